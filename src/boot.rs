@@ -24,6 +24,12 @@ core::arch::global_asm! {r#"
 
     relocated:
         la sp, bootstack_top
+
+        # tp = &HART_LOCALS[a0] (see `crate::smp`)
+        la tp, HART_LOCALS
+        slli t0, a0, {local_shift}
+        add tp, tp, t0
+
         j main
 
     .globl _relocated
@@ -46,4 +52,6 @@ core::arch::global_asm! {r#"
         .zero 2040
         .8byte 0x2000002F  # [VM_BASE, VM_BASE + 1GB) => [PM_BASE, PM_BASE + 1GB)
         .zero 2048
-"#}
+"#,
+    local_shift = const crate::smp::LOCAL_SHIFT,
+}
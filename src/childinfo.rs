@@ -1,6 +1,6 @@
 pub use crate::sync::sema::Semaphore;
 use alloc::sync::Arc;
-use thread::Thread;
+use thread::{Thread, Usage};
 
 #[derive(Clone)]
 pub struct ChildInfo {
@@ -10,6 +10,12 @@ pub struct ChildInfo {
     pub is_waiting: bool,
     pub wait_sema: Arc<Semaphore>,
     pub ptr: Option<Arc<Thread>>,
+    /// Whether it was killed, by the kernel or by `kill`, rather than exiting
+    /// by itself
+    pub killed: bool,
+    /// Resources used by the child and its reaped descendants, set when it
+    /// exits. Added to the parent's `children_usage` once it's waited for.
+    pub usage: Usage,
 }
 
 impl ChildInfo {
@@ -27,6 +33,8 @@ impl ChildInfo {
             is_waiting,
             wait_sema: Arc::new(Semaphore::new(0)),
             ptr: Some(ptr),
+            killed: false,
+            usage: Usage::default(),
         }
     }
 }
\ No newline at end of file
@@ -18,4 +18,10 @@ pub enum OsError {
     ArgumentTooLong = -11,
     InvalidFileMode = -12,
     FileNotOpened = -13,
+    Overloaded = -14,
+    NoSuchProcess = -15,
+    WouldBlock = -16,
+    SwapFull = -17,
+    InvalidSwapSlot = -18,
+    OutOfMemory = -19,
 }
@@ -1,12 +1,18 @@
 use crate::fs::File;
 use alloc::vec::Vec;
 
+/// Open flag that closes the file when the process calls
+/// [`execve`](crate::userproc::execve)
+pub const O_CLOEXEC: usize = 0x800;
+
+#[derive(Clone)]
 pub struct FDInfo {
     pub fd:     isize,
     pub file:   File,
     pub flag:   usize,
 }
 
+#[derive(Clone)]
 pub struct FDList {
     pub list: Vec<FDInfo>,
 }
@@ -36,6 +42,11 @@ impl FDList {
             self.list.push(FDInfo::new(fd, file, flag));
             fd
     }
+    /// Closes the files opened with [`O_CLOEXEC`].
+    pub fn close_on_exec(&mut self) {
+        self.list.retain(|x| x.flag & O_CLOEXEC == 0);
+    }
+
     pub fn get_by_fd(&mut self, fd:isize) -> Option<&mut FDInfo> {
         self
             .list
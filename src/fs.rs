@@ -67,7 +67,6 @@ pub trait Vnode: Sync + Send {
 /// A file descriptor, binding with a [`Vnode`], that has
 /// independent position and permissions. It provides basic
 /// file I/O interface.
-#[derive(Clone)]
 pub struct File {
     vnode: Arc<dyn Vnode>,
     pos: usize,
@@ -127,6 +126,27 @@ impl File {
         self.deny_write = true;
         self.vnode.deny_write();
     }
+
+    /// Opens the vnode of the file again, at position 0 and allowing writes.
+    /// Unlike [`File::clone`], it never takes the inode's lock, so it may be
+    /// called under a spin lock.
+    pub fn reopen(&self) -> Self {
+        Self::new(self.vnode.clone())
+    }
+}
+
+impl Clone for File {
+    /// A clone of a file that denies writes denies them as well.
+    fn clone(&self) -> Self {
+        if self.deny_write {
+            self.vnode.deny_write();
+        }
+        Self {
+            vnode: self.vnode.clone(),
+            pos: self.pos,
+            deny_write: self.deny_write,
+        }
+    }
 }
 
 impl Drop for File {
@@ -1,5 +1,6 @@
 //! On disk file system.
 //!
+mod cache;
 mod dir;
 mod free_map;
 mod inode;
@@ -10,6 +11,8 @@ mod swap;
 pub use self::path::Path;
 // Expose swap utils.
 pub use self::swap::Swap;
+// Expose the buffer cache, mostly for flushing it.
+pub use self::cache::BufferCache;
 
 use alloc::collections::BTreeMap;
 use alloc::sync::{Arc, Weak};
@@ -20,7 +23,7 @@ use self::inode::Inode;
 
 use super::{File, FileSys, Vnode};
 use crate::device::virtio::{Virtio, SECTOR_SIZE};
-use crate::sync::{Lazy, Mutex};
+use crate::sync::{Lazy, Mutex, RwLock};
 use crate::{OsError, Result};
 
 /// Inode number.
@@ -81,7 +84,8 @@ pub struct DiskFs {
     device: &'static Mutex<Virtio>,
     pub(self) free_map: Mutex<FreeMap>,
     pub root_dir: Mutex<RootDir>,
-    inode_table: Mutex<BTreeMap<Inum, Weak<Inode>>>,
+    /// Looked up on every open, and only changed when an inode is first opened
+    inode_table: RwLock<BTreeMap<Inum, Weak<Inode>>>,
 }
 
 impl FileSys for DiskFs {
@@ -89,8 +93,9 @@ impl FileSys for DiskFs {
     type Path = Path;
 
     fn mount(device: Self::Device) -> Result<Self> {
+        BufferCache::start();
         let capacity = device.lock().capacity();
-        let inode_table = Mutex::new(BTreeMap::new());
+        let inode_table = RwLock::new(BTreeMap::new());
         let free_map = Mutex::new({
             let size = capacity as u32;
             if let Ok(loaded) = FreeMap::load(size) {
@@ -120,7 +125,7 @@ impl FileSys for DiskFs {
             };
 
             let weak = Arc::downgrade(&vnode);
-            inode_table.lock().insert(ROOT_DIR_SECTOR, weak);
+            inode_table.write().insert(ROOT_DIR_SECTOR, weak);
             RootDir(File::new(vnode))
         });
         Ok(Self {
@@ -133,13 +138,14 @@ impl FileSys for DiskFs {
 
     fn unmount(&self) {
         let _ = self.free_map.lock().flush();
+        BufferCache::flush();
     }
 
     fn create(&self, id: Self::Path) -> Result<super::File> {
         let vnode = if self.root_dir.lock().exists(&id) {
             let inum = self.root_dir.lock().path2inum(&id).unwrap();
             let vnode =
-                if let Some(arc) = self.inode_table.lock().get(&inum).and_then(Weak::upgrade) {
+                if let Some(arc) = self.inode_table.read().get(&inum).and_then(Weak::upgrade) {
                     arc
                 } else {
                     Inode::open(inum)?
@@ -155,7 +161,7 @@ impl FileSys for DiskFs {
 
             let vnode = Inode::create(sector, start, 0)?;
             let weak = Arc::downgrade(&vnode);
-            self.inode_table.lock().insert(sector, weak);
+            self.inode_table.write().insert(sector, weak);
 
             self.root_dir.lock().insert(&id, sector)?;
             vnode
@@ -170,13 +176,13 @@ impl FileSys for DiskFs {
         }
         // Expect existing.
         let inum = self.root_dir.lock().path2inum(&id).unwrap();
-        if let Some(arc) = self.inode_table.lock().get(&inum).and_then(Weak::upgrade) {
+        if let Some(arc) = self.inode_table.read().get(&inum).and_then(Weak::upgrade) {
             return Ok(File::new(arc));
         }
 
         let vnode = Inode::open(inum)?;
         let weak = Arc::downgrade(&vnode);
-        self.inode_table.lock().insert(inum, weak);
+        self.inode_table.write().insert(inum, weak);
 
         Ok(File::new(vnode))
     }
@@ -187,7 +193,7 @@ impl FileSys for DiskFs {
         let inum = self.root_dir.lock().path2inum(&id)?;
         let mut rootdir = DISKFS.root_dir.lock();
         rootdir.remove(inum)?;
-        if let Some(arc) = self.inode_table.lock().get(&inum).and_then(Weak::upgrade) {
+        if let Some(arc) = self.inode_table.read().get(&inum).and_then(Weak::upgrade) {
             arc.remove();
             return Ok(());
         }
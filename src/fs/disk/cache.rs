@@ -0,0 +1,154 @@
+//! Buffer cache of disk sectors.
+//!
+//! Every sector the file system reads or writes goes through here. Writes stay
+//! in the cache, marked dirty, until the sector is evicted, the flusher thread
+//! wakes up (every [`FLUSH_INTERVAL`] ticks), or [`BufferCache::flush`] is
+//! called. Reading a sector that isn't cached has the next one read ahead in
+//! the background, as files are mostly read sequentially.
+//!
+use alloc::vec::Vec;
+
+use crate::device::virtio::{Virtio, SECTOR_SIZE};
+use crate::sbi::timer::TICKS_PER_SEC;
+use crate::sync::{channel, Lazy, Mutex, OnceCell, Primitive, Sender};
+use crate::thread;
+
+/// Number of sectors cached.
+const CACHE_SECTORS: usize = 64;
+
+/// Ticks between two write-backs by the flusher.
+const FLUSH_INTERVAL: i64 = 3 * TICKS_PER_SEC as i64;
+
+struct Buffer {
+    sector: Option<u64>,
+    dirty: bool,
+    /// Used since the clock hand last passed
+    accessed: bool,
+    data: [u8; SECTOR_SIZE],
+}
+
+struct Cache {
+    buffers: Vec<Buffer>,
+    /// Next buffer the clock hand looks at
+    hand: usize,
+}
+
+impl Cache {
+    /// The buffer holding `sector`, which is read from the disk if it isn't
+    /// cached and `load` is set. Otherwise its data is left to the caller.
+    fn get(&mut self, sector: u64, load: bool) -> &mut Buffer {
+        let index = match self.buffers.iter().position(|b| b.sector == Some(sector)) {
+            Some(index) => index,
+            None => {
+                let index = self.evict();
+                let buffer = &mut self.buffers[index];
+                buffer.sector = Some(sector);
+                if load {
+                    Virtio::read_sector(sector, &mut buffer.data);
+                }
+                index
+            }
+        };
+
+        let buffer = &mut self.buffers[index];
+        buffer.accessed = true;
+        buffer
+    }
+
+    /// Frees a buffer with the clock algorithm, writing it back if dirty.
+    fn evict(&mut self) -> usize {
+        loop {
+            let index = self.hand;
+            self.hand = (self.hand + 1) % self.buffers.len();
+
+            let buffer = &mut self.buffers[index];
+            if buffer.sector.is_some() && buffer.accessed {
+                buffer.accessed = false;
+                continue;
+            }
+            if buffer.dirty {
+                Virtio::write_sector(buffer.sector.unwrap(), &buffer.data);
+                buffer.dirty = false;
+            }
+            buffer.sector = None;
+            return index;
+        }
+    }
+}
+
+static CACHE: Lazy<Mutex<Cache, Primitive>> = Lazy::new(|| {
+    let buffers = (0..CACHE_SECTORS)
+        .map(|_| Buffer {
+            sector: None,
+            dirty: false,
+            accessed: false,
+            data: [0; SECTOR_SIZE],
+        })
+        .collect();
+    Mutex::new(Cache { buffers, hand: 0 })
+});
+
+/// Sectors to read ahead, handled by the read-ahead thread.
+static READAHEAD: OnceCell<Sender<u64>> = OnceCell::new();
+
+pub struct BufferCache;
+
+impl BufferCache {
+    /// Starts the flusher and read-ahead threads.
+    pub fn start() {
+        thread::spawn("flusher", || loop {
+            thread::sleep(FLUSH_INTERVAL);
+            Self::flush();
+        });
+
+        let (tx, rx) = channel(8);
+        READAHEAD.init(|| tx);
+        thread::spawn("readahead", move || {
+            while let Some(sector) = rx.recv() {
+                Self::prefetch(sector);
+            }
+        });
+    }
+
+    /// Read a sector through the cache.
+    pub fn read_sector(sector: u64, buf: &mut [u8; SECTOR_SIZE]) {
+        let mut cache = CACHE.lock();
+        let cached = cache.buffers.iter().any(|b| b.sector == Some(sector));
+        buf.copy_from_slice(&cache.get(sector, true).data);
+        drop(cache);
+
+        if !cached {
+            // Dropped if the read-ahead thread is lagging behind.
+            let _ = READAHEAD.get().try_send(sector + 1);
+        }
+    }
+
+    /// Write a sector through the cache. It reaches the disk later on.
+    pub fn write_sector(sector: u64, buf: &[u8; SECTOR_SIZE]) {
+        let mut cache = CACHE.lock();
+        let buffer = cache.get(sector, false);
+        buffer.data.copy_from_slice(buf);
+        buffer.dirty = true;
+    }
+
+    /// Write all dirty sectors back to the disk.
+    pub fn flush() {
+        let mut cache = CACHE.lock();
+        for buffer in cache.buffers.iter_mut().filter(|b| b.dirty) {
+            Virtio::write_sector(buffer.sector.unwrap(), &buffer.data);
+            buffer.dirty = false;
+        }
+    }
+
+    /// Caches `sector` if it isn't yet, without counting it as used, so it's
+    /// the first to go if no one reads it.
+    fn prefetch(sector: u64) {
+        if sector >= Virtio::get().lock().capacity() {
+            return;
+        }
+        let mut cache = CACHE.lock();
+        if cache.buffers.iter().all(|b| b.sector != Some(sector)) {
+            cache.get(sector, true).accessed = false;
+        }
+    }
+}
@@ -5,9 +5,11 @@ use core::convert::TryInto;
 use core::ops::{Deref, Drop};
 use core::{cmp, mem};
 
+use super::cache::BufferCache;
 use super::{bytes_to_sectors, Inum, DISKFS};
-use crate::device::virtio::{Virtio, SECTOR_SIZE};
+use crate::device::virtio::SECTOR_SIZE;
 use crate::fs::Vnode;
+use crate::mem::pagecache::PageCache;
 use crate::mem::{Translate, PG_MASK, PG_SIZE};
 use crate::sync::Mutex;
 use crate::{OsError, Result};
@@ -88,15 +90,18 @@ impl Inode {
             padding: [0; INODE_PADDING],
         };
         unsafe {
-            Virtio::write_sector(sector as _, mem::transmute(&disk_inode));
+            BufferCache::write_sector(sector as _, mem::transmute(&disk_inode));
         }
 
         // Zero the file.
         let zeros = [0; SECTOR_SIZE];
         for i in 0..sector_num {
-            Virtio::write_sector((start + i) as _, &zeros);
+            BufferCache::write_sector((start + i) as _, &zeros);
         }
 
+        // Pages of a removed file may be cached under the same number.
+        PageCache::invalidate(sector as _);
+
         let desc = InodeDesc::new(sector, 0);
         Ok(Arc::from(Self(Mutex::new((desc, disk_inode)))))
     }
@@ -117,7 +122,7 @@ impl Inode {
             padding: [0; INODE_PADDING],
         };
         unsafe {
-            Virtio::read_sector(sector as _, mem::transmute(&mut data));
+            BufferCache::read_sector(sector as _, mem::transmute(&mut data));
         }
 
         if data.inner.magic != INODE_MAGIC {
@@ -132,7 +137,7 @@ impl Inode {
         let flush_len = |a: &InodeDesc, b: &mut DiskInode| {
             b.inner.len = newlen;
             unsafe {
-                Virtio::write_sector(a.sector as _, &mem::transmute_copy(b));
+                BufferCache::write_sector(a.sector as _, &mem::transmute_copy(b));
             }
         };
 
@@ -177,8 +182,8 @@ impl Inode {
                     // Copy.
                     let mut bounce = [0u8; SECTOR_SIZE];
                     for i in 0..bytes_to_sectors(oldlen as _) {
-                        Virtio::read_sector((old_start + i) as _, &mut bounce);
-                        Virtio::write_sector((new_start + i) as _, &bounce);
+                        BufferCache::read_sector((old_start + i) as _, &mut bounce);
+                        BufferCache::write_sector((new_start + i) as _, &bounce);
                     }
                     freemap.dealloc(old_start, bytes_to_sectors(oldlen as _));
                     desc.shrink_len = 0;
@@ -227,7 +232,7 @@ impl Vnode for Inode {
             let page_off = (buf.as_ptr() as usize + bytes_read) & PG_MASK;
 
             if (chunk_size == SECTOR_SIZE) && (page_off <= PG_SIZE - SECTOR_SIZE) {
-                // Virtio only supports kernel buffers.
+                // Faulting under the buffer cache lock would deadlock.
                 // So we need to convert the possible user buffer into kernel buffer.
                 let buf_kvm: &mut [u8; SECTOR_SIZE] = (&mut buf
                     [bytes_read..bytes_read + SECTOR_SIZE])
@@ -235,11 +240,11 @@ impl Vnode for Inode {
                     .ok_or(OsError::BadPtr)?
                     .try_into()
                     .unwrap();
-                Virtio::read_sector(sector as _, buf_kvm);
+                BufferCache::read_sector(sector as _, buf_kvm);
             } else {
                 // We need a bounce buffer.
                 let mut bounce = [0; SECTOR_SIZE];
-                Virtio::read_sector(sector as _, &mut bounce);
+                BufferCache::read_sector(sector as _, &mut bounce);
                 buf[bytes_read..bytes_read + chunk_size]
                     .copy_from_slice(&bounce[sector_offset..sector_offset + chunk_size]);
             }
@@ -257,6 +262,7 @@ impl Vnode for Inode {
         if self.0.lock().0.deny_write > 0 {
             return Err(OsError::InvalidFileMode);
         }
+        PageCache::invalidate(self.inum());
 
         let mut bytes_written = 0;
         let mut buf_left = buf.len();
@@ -290,7 +296,7 @@ impl Vnode for Inode {
             let page_off = (buf.as_ptr() as usize + bytes_written) & PG_MASK;
 
             if (chunk_size == SECTOR_SIZE) && (page_off <= PG_SIZE - SECTOR_SIZE) {
-                // Virtio only supports kernel buffers.
+                // Faulting under the buffer cache lock would deadlock.
                 // So we need to convert the possible user buffer into kernel buffer.
                 let buf_kvm: &[u8; SECTOR_SIZE] = (&buf
                     [bytes_written..bytes_written + SECTOR_SIZE])
@@ -298,14 +304,14 @@ impl Vnode for Inode {
                     .ok_or(OsError::BadPtr)?
                     .try_into()
                     .unwrap();
-                Virtio::write_sector(sector as _, buf_kvm);
+                BufferCache::write_sector(sector as _, buf_kvm);
             } else {
                 // We need a bounce buffer, preserving old bytes which should not be written.
                 let mut bounce = [0; SECTOR_SIZE];
-                Virtio::read_sector(sector as _, &mut bounce);
+                BufferCache::read_sector(sector as _, &mut bounce);
                 bounce[sector_offset..sector_offset + chunk_size]
                     .copy_from_slice(&buf[bytes_written..bytes_written + chunk_size]);
-                Virtio::write_sector(sector as _, &bounce);
+                BufferCache::write_sector(sector as _, &bounce);
             }
 
             buf_left -= chunk_size;
@@ -317,6 +323,7 @@ impl Vnode for Inode {
     }
 
     fn resize(&self, newlen: usize) -> Result<()> {
+        PageCache::invalidate(self.inum());
         let mut guard = self.0.lock();
         let (desc, data) = &mut *guard;
         Self::resize_inner(desc, data, newlen)
@@ -6,10 +6,12 @@ use super::DISKFS;
 use crate::fs::{File, FileSys};
 use crate::io::Seek;
 use crate::mem::PG_SIZE;
-use crate::sync::{Lazy, Mutex, MutexGuard, Primitive};
+use crate::sync::{Lazy, Mutex, MutexGuard, Primitive, Spin};
+use crate::{OsError, Result};
 
 use crate::io::{Read, SeekFrom, Write};
-use alloc::vec::Vec;
+use alloc::boxed::Box;
+use alloc::vec;
 
 pub struct Swap;
 
@@ -21,12 +23,78 @@ static SWAPFILE: Lazy<Mutex<File, Primitive>> = Lazy::new(|| {
     )
 });
 
-static SWAPFILE_VEC: Lazy<Mutex<Vec<usize>, Primitive>> = Lazy::new(|| {
-    let mut v = Vec::new();
-    for i in (0..Swap::len()).step_by(PG_SIZE) {
-        v.push(i);
+/// Usage of the swap file, in pages.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SwapStats {
+    pub slots: usize,
+    pub used: usize,
+    /// Most slots ever used at the same time
+    pub peak: usize,
+    /// Allocations failed for lack of a free slot
+    pub failures: usize,
+}
+
+/// Kept apart from the map, so that it can be queried before the swap file
+/// is opened, or if there is none.
+static SWAPSTATS: Lazy<Mutex<SwapStats, Spin>> = Lazy::new(Mutex::default);
+
+/// Bitmap of swap slots in use, one bit per page of the swap file.
+struct SwapMap {
+    bits: Box<[u8]>,
+    slots: usize,
+    /// Where to start looking for a free slot
+    next: usize,
+}
+
+impl SwapMap {
+    fn new(slots: usize) -> Self {
+        SWAPSTATS.lock().slots = slots;
+        Self {
+            bits: vec![0; (slots + 7) / 8].into(),
+            slots,
+            next: 0,
+        }
+    }
+
+    fn get(&self, slot: usize) -> bool {
+        self.bits[slot / 8] & (1 << slot % 8) != 0
+    }
+
+    fn alloc(&mut self) -> Option<usize> {
+        let mut stats = SWAPSTATS.lock();
+        let Some(slot) = (0..self.slots)
+            .map(|i| (self.next + i) % self.slots)
+            .find(|&slot| !self.get(slot))
+        else {
+            stats.failures += 1;
+            return None;
+        };
+
+        self.bits[slot / 8] |= 1 << slot % 8;
+        self.next = (slot + 1) % self.slots;
+        stats.used += 1;
+        stats.peak = stats.peak.max(stats.used);
+        Some(slot)
     }
-    Mutex::new(v)
+
+    fn free(&mut self, slot: usize) {
+        assert!(
+            slot < self.slots && self.get(slot),
+            "freeing swap slot {} not in use",
+            slot
+        );
+        self.bits[slot / 8] &= !(1 << slot % 8);
+        SWAPSTATS.lock().used -= 1;
+    }
+}
+
+/// Slots are taken and given back under the locks of page tables, so it spins.
+/// Opening the swap file can't, which is what [`Swap::init`] is for.
+static SWAPMAP: Lazy<Mutex<SwapMap, Spin>> = Lazy::new(|| {
+    let slots = DISKFS
+        .open(".glbswap".into())
+        .map_or(0, |file| file.len().unwrap() / PG_SIZE);
+    Mutex::new(SwapMap::new(slots))
 });
 
 impl Swap {
@@ -44,31 +112,56 @@ impl Swap {
         SWAPFILE.lock()
     }
 
-    pub fn new_page() -> usize {
-        let mut vec = SWAPFILE_VEC.lock();
-        vec.pop().unwrap()
+    /// Sets up the slots of the swap file, if not done yet. It must be called
+    /// before the first [`Swap::new_page`] under a spin lock. Without a swap
+    /// file, there are no slots.
+    pub fn init() {
+        SWAPMAP.get();
+    }
+
+    /// Allocates a page in the swap file, returning its offset. Fails with
+    /// [`OsError::SwapFull`] if none is free.
+    pub fn new_page() -> Result<usize> {
+        SWAPMAP
+            .lock()
+            .alloc()
+            .map(|slot| slot * PG_SIZE)
+            .ok_or(OsError::SwapFull)
     }
 
-    pub fn push_page(page: usize) {
-        let mut vec = SWAPFILE_VEC.lock();
-        vec.push(page);
+    /// Frees the page at `pos`, allocated by [`Swap::new_page`].
+    pub fn free_page(pos: usize) {
+        SWAPMAP.lock().free(pos / PG_SIZE);
     }
 
-    pub fn read_page(pos: usize, buf: &mut [u8]) -> usize {
+    /// Counts the slots of the swap file, setting them up first if needed.
+    pub fn stats() -> SwapStats {
+        Self::init();
+        *SWAPSTATS.lock()
+    }
+
+    /// Reads from `pos` into `buf`, which must be within a page of the swap
+    /// file. Returns how many bytes are read.
+    pub fn read_page(pos: usize, buf: &mut [u8]) -> Result<usize> {
         let mut file = SWAPFILE.lock();
-        if (pos >= file.len().unwrap()) {
-            panic!("read invalid swap page");
-        }
-        file.seek(SeekFrom::Start(pos));
-        file.read(buf).unwrap()
+        Self::check(&file, pos, buf.len())?;
+        file.seek(SeekFrom::Start(pos))?;
+        file.read(buf)
     }
 
-    pub fn write_page(pos: usize, buf: &[u8]) {
+    /// Writes `buf` at `pos`, which must be within a page of the swap file.
+    pub fn write_page(pos: usize, buf: &[u8]) -> Result<()> {
         let mut file = SWAPFILE.lock();
-        if (pos >= file.len().unwrap()) {
-            panic!("write invalid swap page");
+        Self::check(&file, pos, buf.len())?;
+        file.seek(SeekFrom::Start(pos))?;
+        file.write_all(buf)
+    }
+
+    fn check(file: &File, pos: usize, len: usize) -> Result<()> {
+        let in_page = pos % PG_SIZE + len <= PG_SIZE;
+        if !in_page || pos + len > file.len()? {
+            return Err(OsError::InvalidSwapSlot);
         }
-        file.seek(SeekFrom::Start(pos));
-        file.write(buf);
+        Ok(())
     }
 }
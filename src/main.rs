@@ -15,8 +15,10 @@ pub mod error;
 pub mod fs;
 pub mod io;
 pub mod mem;
+pub mod smp;
 pub mod sync;
 pub mod thread;
+pub mod trace;
 pub mod trap;
 pub mod userproc;
 
@@ -41,6 +43,7 @@ extern "C" {
     fn ebss();
     fn ekernel();
     fn bootstack();
+    fn bootstack_top();
 }
 
 pub type Result<T> = core::result::Result<T, OsError>;
@@ -108,14 +111,14 @@ pub extern "C" fn main(hart_id: usize, dtb: usize) -> ! {
     // Init timer & external interrupt
     sbi::interrupt::init();
 
+    #[cfg(feature = "lockdep")]
+    sync::lockdep::enable();
+
+    // Bring up the other harts
+    smp::start_harts(&devtree);
+
     #[cfg(feature = "test")]
-    {
-        use alloc::sync::Arc;
-        let sema = Arc::new(sync::Semaphore::new(0));
-        let sema2 = sema.clone();
-        thread::spawn("test", move || crate::test::main(sema2, _bootargs));
-        sema.down();
-    }
+    thread::spawn("test", move || crate::test::main(_bootargs)).join();
 
     #[cfg(feature = "shell")]
     {
@@ -158,6 +161,7 @@ pub extern "C" fn main(hart_id: usize, dtb: usize) -> ! {
             match input.as_str() {
                 "exit" => break,
                 "whoami" => kprintln!("2200013188"),
+                "trace" => trace::dump(),
                 "" => {}
                 _ => kprintln!("Invalid command"),
             }
@@ -166,6 +170,9 @@ pub extern "C" fn main(hart_id: usize, dtb: usize) -> ! {
         }
     }
 
+    #[cfg(feature = "mem-stats")]
+    mem::palloc::replacement::STATS.dump();
+
     DISKFS.unmount();
 
     kprintln!("Goodbye, World!");
@@ -185,6 +192,9 @@ unsafe fn panic(info: &core::panic::PanicInfo) -> ! {
     // Report the reason for invoking `panic`
     kprintln!("{}", info);
 
+    #[cfg(feature = "trace")]
+    trace::dump();
+
     sbi::reset(
         sbi::system_reset::Type::Shutdown,
         sbi::system_reset::Reason::SystemFailure,
@@ -9,6 +9,7 @@
 
 pub mod layout;
 pub mod malloc;
+pub mod pagecache;
 pub mod pagetable;
 pub mod palloc;
 pub mod userbuf;
@@ -27,7 +28,7 @@ pub use self::utils::*;
 use self::palloc::USER_POOL_LIMIT;
 
 pub fn get_pte(va: usize) -> Option<Entry> {
-    match crate::thread::Manager::get().current.lock().pagetable {
+    match crate::thread::current().pagetable {
         Some(ref pt) => pt.lock().get_pte(va).copied(),
         None => KernelPgTable::get().get_pte(va).copied(),
     }
@@ -8,7 +8,7 @@ use core::sync::atomic::{AtomicUsize, Ordering::Relaxed};
 
 use crate::mem::palloc::Palloc;
 use crate::mem::utils::*;
-use crate::sync::{Intr, Lazy, Mutex};
+use crate::sync::{Lazy, Mutex, Spin};
 
 const ARENA_MAGIC: u32 = 0x9a548eed;
 const MAX_BLKSIZE: usize = PG_SIZE / 4;
@@ -125,7 +125,7 @@ impl Desc {
 /// they are assigned to ["descriptors"](`Desc`) that manages blocks of that size.
 /// Otherwise, the request will go directly to [`Palloc`].
 pub struct Heap {
-    descs: [Mutex<Desc, Intr>; 8],
+    descs: [Mutex<Desc, Spin>; 8],
     /// The sum of requests that can't fit in any descriptors,
     /// namely requests that are larger than [`MAX_BLKSIZE`])
     spilled_alloc: AtomicUsize,
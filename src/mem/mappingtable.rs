@@ -14,6 +14,9 @@ pub struct MapInfo {
     pub filesize: usize,
     pub memsize: usize,
     pub flags: PTEFlags,
+    /// Whether changes are written back to the file. Pages of private
+    /// mappings only ever go to the swap.
+    pub shared: bool,
 }
 
 pub struct MappingTable {
@@ -38,6 +41,22 @@ impl MapInfo {
             filesize,
             memsize,
             flags,
+            shared: false,
+        }
+    }
+
+    /// An empty heap at `va`. Its pages are anonymous: zero-filled when first
+    /// touched, and kept in the swap when evicted.
+    pub fn heap(va: usize) -> Self {
+        MapInfo::new(-1, None, 0, va, 0, 0, PTEFlags::U | PTEFlags::R | PTEFlags::W)
+    }
+
+    /// A copy of the mapping whose file is reopened rather than cloned, which
+    /// may be made under a spin lock (see [`File::reopen`]).
+    pub fn reopen(&self) -> Self {
+        MapInfo {
+            file: self.file.as_ref().map(File::reopen),
+            ..*self
         }
     }
 
@@ -48,6 +67,10 @@ impl MapInfo {
     pub fn contains(&self, va: usize) -> bool {
         va >= self.va && va < self.va_end().ceil()
     }
+
+    pub fn overlaps(&self, l: usize, r: usize) -> bool {
+        !(r <= self.va || l >= self.va_end().ceil())
+    }
 }
 
 impl MappingTable {
@@ -55,43 +78,30 @@ impl MappingTable {
         MappingTable { list: Vec::new() } 
     }
 
-    pub fn map(
-        &mut self,
-        file: File,
-        offset: usize,
-        va: usize,
-        filesize: usize,
-        memsize: usize,
-        flags: PTEFlags,
-    ) -> isize {
-        let mapid = self
+    /// Adds `mapinfo` under a new mapping id, which is returned.
+    pub fn map(&mut self, mut mapinfo: MapInfo) -> isize {
+        mapinfo.mapid = self
             .list
             .iter()
             .map(|x| x.mapid)
             .max()
             .unwrap_or(0) + 1; // oh, we can also store a max here
-        self.list.push(MapInfo::new(
-            mapid,
-            Some(file),
-            offset,
-            va,
-            filesize,
-            memsize,
-            flags,
-        ));
+        let mapid = mapinfo.mapid;
+        self.list.push(mapinfo);
         mapid
     }
 
     pub fn va_range_check(&mut self, l: usize, r: usize) -> bool {
-        for mi in self.list.iter() {
-            if !(r <= mi.va || l >= mi.va_end().ceil()) {
-                return false;
-            }
-        }
-        true
+        !self.list.iter().any(|mi| mi.overlaps(l, r))
     }
 
     pub fn get_by_id(&mut self, mapid:isize) -> Option<&mut MapInfo> {
         self.list.iter_mut().find(|x| x.mapid == mapid)
     }    
+
+    /// The heap of the process. Anonymous mappings have no file either, but
+    /// they are made by the process, with an id of their own.
+    pub fn heap(&mut self) -> Option<&mut MapInfo> {
+        self.list.iter_mut().find(|x| x.mapid == -1 && x.file.is_none())
+    }
 }
\ No newline at end of file
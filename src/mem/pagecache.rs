@@ -0,0 +1,131 @@
+//! Page cache of executables.
+//!
+//! Read-only pages of an executable are the same in every process running it,
+//! so they are read from disk once and the frame is shared by all of them.
+//! Pages are keyed by the inode number and the offset in the file.
+//!
+//! The cache holds a reference of its own to each frame (see
+//! [`GlobalFrameTable::share`]), so a page stays around after the last process
+//! using it exits, ready for the next one. Frames only the cache refers to are
+//! given back when the user pool runs out ([`PageCache::shrink`]).
+//!
+//! Hits and pages read in are counted, see [`PageCache::stats`]. Each hit is a
+//! disk read and a frame saved.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+
+use crate::mem::palloc::frame::GlobalFrameTable;
+use crate::mem::palloc::UserPool;
+use crate::mem::PhysAddr;
+use crate::sync::{Lazy, Mutex, Spin};
+
+struct Page {
+    pa: PhysAddr,
+    /// Bytes read from the file, the rest of the page is zeroed
+    len: usize,
+}
+
+/// Pages keyed by (inode number, file offset)
+type Pages = BTreeMap<(usize, usize), Page>;
+
+static PAGES: Lazy<Mutex<Pages, Spin>> = Lazy::new(Mutex::default);
+
+/// Activity of the page cache
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CacheStats {
+    /// Pages in the cache
+    pub pages: usize,
+    /// Lookups that found their page
+    pub hits: usize,
+    /// Pages read from disk and cached
+    pub reads: usize,
+}
+
+static HITS: AtomicUsize = AtomicUsize::new(0);
+static READS: AtomicUsize = AtomicUsize::new(0);
+
+pub struct PageCache;
+
+impl PageCache {
+    /// Looks up the page at `offset` of inode `inum`, holding `len` bytes of
+    /// the file. On a hit, the frame gets a reference for the caller to map.
+    pub fn get(inum: usize, offset: usize, len: usize) -> Option<PhysAddr> {
+        let pages = PAGES.lock();
+        let page = pages.get(&(inum, offset)).filter(|p| p.len == len)?;
+        GlobalFrameTable::share(page.pa.value());
+        HITS.fetch_add(1, SeqCst);
+        Some(page.pa)
+    }
+
+    /// Caches the frame at `pa`, just read from the file by the caller, who
+    /// keeps mapping it. If another process has cached the same page in the
+    /// meantime, `pa` is freed and that one is returned instead.
+    pub fn insert(inum: usize, offset: usize, len: usize, pa: PhysAddr) -> PhysAddr {
+        if let Some(cached) = Self::get(inum, offset, len) {
+            unsafe { UserPool::dealloc_pages(pa.into_va() as *mut _, 1) };
+            return cached;
+        }
+
+        READS.fetch_add(1, SeqCst);
+        // A page of a different length is left to its current users.
+        PAGES.lock().entry((inum, offset)).or_insert_with(|| {
+            GlobalFrameTable::share(pa.value());
+            Page { pa, len }
+        });
+        pa
+    }
+
+    pub fn stats() -> CacheStats {
+        CacheStats {
+            pages: PAGES.lock().len(),
+            hits: HITS.load(SeqCst),
+            reads: READS.load(SeqCst),
+        }
+    }
+
+    /// Drops all pages of inode `inum`, whose content is changing. Processes
+    /// mapping them keep their frames.
+    pub fn invalidate(inum: usize) {
+        let dropped: Vec<_> = {
+            let mut pages = PAGES.lock();
+            let offsets: Vec<_> = pages
+                .range((inum, 0)..=(inum, usize::MAX))
+                .map(|(key, _)| *key)
+                .collect();
+            offsets
+                .into_iter()
+                .filter_map(|key| pages.remove(&key))
+                .collect()
+        };
+        Self::release(dropped);
+    }
+
+    /// Frees the pages no process maps. Returns whether any was freed.
+    pub fn shrink() -> bool {
+        let dropped: Vec<_> = {
+            let mut pages = PAGES.lock();
+            let unused: Vec<_> = pages
+                .iter()
+                .filter(|(_, page)| !GlobalFrameTable::is_shared(page.pa.value()))
+                .map(|(key, _)| *key)
+                .collect();
+            unused
+                .into_iter()
+                .filter_map(|key| pages.remove(&key))
+                .collect()
+        };
+        let freed = !dropped.is_empty();
+        Self::release(dropped);
+        freed
+    }
+
+    /// Gives up the references of the cache, which can't be done under
+    /// [`PAGES`] as the user pool lock may block.
+    fn release(pages: Vec<Page>) {
+        for page in pages {
+            unsafe { UserPool::dealloc_pages(page.pa.into_va() as *mut _, 1) };
+        }
+    }
+}
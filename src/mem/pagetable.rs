@@ -118,6 +118,27 @@ impl PageTable {
         })
     }
 
+    /// Calls `f` on the entry of each mapped user page, along with its virtual address.
+    pub fn for_each_user_page(&self, mut f: impl FnMut(usize, &mut Entry)) {
+        for (i2, l2) in self.entries.iter().enumerate() {
+            if !l2.is_valid() || l2.is_global() || l2.is_leaf() {
+                continue;
+            }
+            let l1_table = unsafe { PageTable::from_raw(l2.pa().into_va() as *mut _) };
+            for (i1, l1) in l1_table.entries.iter().enumerate() {
+                if !l1.is_valid() || l1.is_leaf() {
+                    continue;
+                }
+                let l0_table = unsafe { PageTable::from_raw(l1.pa().into_va() as *mut _) };
+                for (i0, l0) in l0_table.entries.iter_mut().enumerate() {
+                    if l0.is_valid() && l0.is_user() {
+                        f(i2 << 30 | i1 << 21 | i0 << PG_SHIFT, l0);
+                    }
+                }
+            }
+        }
+    }
+
     /// Free all memory used by this pagetable back to where they were allocated.
     pub unsafe fn destroy(&mut self) {
         unsafe fn destroy_imp(pgt: &mut PageTable, level: usize) {
@@ -28,6 +28,8 @@ bitflags::bitflags! {
         const A = 0b0100_0000;
         /// Dirty
         const D = 0b1000_0000;
+        /// Copy-on-write, shared read-only until written (software-defined)
+        const COW = 0b1_0000_0000;
     }
 }
 
@@ -38,7 +40,7 @@ impl Entry {
         Entry((((pa.value() >> PG_SHIFT) & PPN_MASK) << Self::FLAG_SHIFT) | flags.bits())
     }
 
-    fn flag(&self) -> PTEFlags {
+    pub fn flag(&self) -> PTEFlags {
         PTEFlags::from_bits_truncate(self.0)
     }
 
@@ -79,11 +81,33 @@ impl Entry {
         self.flag().contains(PTEFlags::A)
     }
 
+    pub fn is_writable(&self) -> bool {
+        self.flag().contains(PTEFlags::W)
+    }
+
+    pub fn is_cow(&self) -> bool {
+        self.flag().contains(PTEFlags::COW)
+    }
+
+    /// Write-protects a writable page until it's copied, see [`is_cow`](Self::is_cow).
+    pub fn set_cow(&mut self) {
+        self.0 = (self.0 & !PTEFlags::W.bits) | PTEFlags::COW.bits;
+    }
+
+    /// Makes a copy-on-write page writable again.
+    pub fn clear_cow(&mut self) {
+        self.0 = (self.0 & !PTEFlags::COW.bits) | PTEFlags::W.bits;
+    }
+
     // TODO: should implement in pagetable, and re-activate
     pub fn set_invalid(&mut self) {
         self.0 &= !PTEFlags::V.bits;
     }
 
+    pub fn set_valid(&mut self) {
+        self.0 |= PTEFlags::V.bits;
+    }
+
     pub fn set_unaccessed(&mut self) {
         self.0 &= !PTEFlags::A.bits;
     }
@@ -1,23 +1,30 @@
 //! Global Page Allocator
 
 use core::cmp::min;
+use core::ptr;
 
 use crate::mem::mappingtable::{MapInfo, MappingTable};
+use crate::mem::pagecache::PageCache;
 use crate::mem::{pagetable, utils::*};
-use crate::sync::{Intr, Lazy, Mutex};
+use crate::sync::{Lazy, Mutex, MutexGuard, Spin};
 
 use core::array;
 use alloc::collections::VecDeque;
 use alloc::sync::{Arc, Weak};
+use alloc::vec;
 use crate::fs::disk::Swap;
+use crate::fs::File;
 use crate::io::{Seek, SeekFrom, Read, Write};
 use crate::sync::Primitive;
 use crate::thread::{current, Thread};
+use crate::userproc::futex;
 use super::{PTEFlags, VM_OFFSET};
 
 pub mod frame;
+pub mod replacement;
 
 use mem::palloc::frame::*;
+use mem::palloc::replacement::{Policy, ReplacementPolicy, Stats, MAX_ROUNDS, STATS};
 
 // BuddyAllocator allocates at most `1<<MAX_ORDER` pages at a time
 const MAX_ORDER: usize = 8;
@@ -127,7 +134,7 @@ impl BuddyAllocator {
 }
 
 /// Wraps the buddy allocator
-pub struct Palloc(Lazy<Mutex<BuddyAllocator, Intr>>);
+pub struct Palloc(Lazy<Mutex<BuddyAllocator, Spin>>);
 
 unsafe impl Sync for Palloc {}
 
@@ -147,34 +154,43 @@ impl Palloc {
         Self::instance().lock().dealloc(ptr, n)
     }
 
-    fn instance() -> &'static Mutex<BuddyAllocator, Intr> {
+    fn instance() -> &'static Mutex<BuddyAllocator, Spin> {
         static PALLOC: Palloc = Palloc(Lazy::new(|| Mutex::new(BuddyAllocator::empty())));
 
         &PALLOC.0
     }
 }
 
-pub struct UserPool(Lazy<Mutex<BuddyAllocator, Primitive>>);
+pub struct UserPool(Lazy<Mutex<BuddyAllocator, Spin>>);
 
 unsafe impl Sync for UserPool {}
 
 impl UserPool {
-    /// Allocate n pages of consecutive space
+    /// Allocate n pages of consecutive space. Returns null if there is no
+    /// room even after evicting a page.
     pub unsafe fn alloc_pages(n: usize) -> *mut u8 {
         let mut guard = Self::instance().lock();
         match guard.alloc(n) {
             Some(ptr) => ptr,
             _ => {
                 guard.release();
-                swap_page();
+                // Cached pages no one maps go first, they are never written back.
+                if !PageCache::shrink() {
+                    swap_page();
+                }
                 guard.acquire();
-                guard.alloc(n).unwrap()
+                guard.alloc(n).unwrap_or(ptr::null_mut())
             }
         }
     }
 
-    /// Free n pages of memory starting at `ptr`
+    /// Free n pages of memory starting at `ptr`. A single page shared by
+    /// forked processes or the page cache is only freed once none of them
+    /// refers to it.
     pub unsafe fn dealloc_pages(ptr: *mut u8, n: usize) {
+        if n == 1 && !GlobalFrameTable::unshare(PhysAddr::from(ptr).value()) {
+            return;
+        }
         let mut guard = Self::instance().lock();
         guard.dealloc(ptr, n);
     }
@@ -184,134 +200,227 @@ impl UserPool {
         Self::instance().lock().insert_range(start, end);
     }
 
-    fn instance() -> &'static Mutex<BuddyAllocator, Primitive> {
+    fn instance() -> &'static Mutex<BuddyAllocator, Spin> {
         static USERPOOL: UserPool = UserPool(Lazy::new(|| Mutex::new(BuddyAllocator::empty())));
 
         &USERPOOL.0
     }
 }
 
+static POLICY: Lazy<Mutex<Policy, Spin>> = Lazy::new(Mutex::default);
+
+/// Held for a whole eviction, including writing the victim out, during which
+/// its page is neither mapped nor readable from where it's going.
+static EVICTION: Lazy<Mutex<(), Primitive>> = Lazy::new(Mutex::default);
+
+/// Waits for the eviction in progress, and holds off others until the guard
+/// is dropped. Pages can't be allocated meanwhile.
+pub fn pause_evictions() -> MutexGuard<'static, (), Primitive> {
+    EVICTION.lock()
+}
+
+/// Where a dirty victim page is written out
+enum Target {
+    /// A swap slot, recorded in the owner's supplementary page table already
+    Swap(usize),
+    /// Its place in the file of a shared mapping
+    File {
+        file: File,
+        offset: usize,
+        owner: Arc<Thread>,
+        va: usize,
+        flags: PTEFlags,
+    },
+}
+
+/// What became of a frame looked at by [`pick`]
+enum Pick {
+    Kept,
+    Clean,
+    /// The first `usize` bytes of the page are to be written out.
+    Dirty(Target, usize),
+}
+
+/// Evicts a page chosen by the replacement policy. Returns `false` if none
+/// can be evicted.
+///
+/// The victim is chosen and unmapped under spin locks, and its contents are
+/// written out from a copy once they're released.
 fn swap_page() -> bool {
-    let mut frame_table = GlobalFrameTable::instance().lock();
-    let size = frame_table.used_pages.len();
-    for __ in 0..size*2 {
-        let index = frame_table.used_pages.pop_front().unwrap();
-        let entry = frame_table.entries.get_mut(index).unwrap();
-        let thread = entry.as_ref().unwrap().thread.upgrade();
-
-        let thread = match thread {
-            None => continue,
-            Some(x) => x,
-        };
-        
-        let va = entry.as_ref().unwrap().va;
-        let pt = match thread.pagetable.as_ref() {
-            Some(x) => x.lock(),
-            _ => {
-                frame_table.used_pages.push_back(index);
-                continue;
+    let _eviction = EVICTION.lock();
+    Swap::init();
+    let mut buf = vec![0u8; PG_SIZE];
+
+    let mut round = 0;
+    // Frames left to go in this round.
+    let mut left = GlobalFrameTable::instance().lock().used_pages.len();
+    while round < MAX_ROUNDS {
+        if left == 0 {
+            round += 1;
+            left = GlobalFrameTable::instance().lock().used_pages.len();
+            continue;
+        }
+        left -= 1;
+
+        match pick(round, &mut buf) {
+            Pick::Kept => continue,
+            Pick::Clean => (),
+            Pick::Dirty(target, size) => {
+                Stats::count(&STATS.writebacks);
+                write_out(target, &buf[..size]);
             }
-        };
+        }
+        Stats::count(&STATS.evictions);
+        return true;
+    }
+    // Memory exhausted.
+    false
+}
 
-        let pte = pt.get_pte_mut(va).unwrap();
-        if pte.is_accessed() {
-            pte.set_unaccessed();
+/// Takes the next frame in the queue, and evicts its page if the policy agrees
+/// and the owner's tables aren't in use on another hart. A dirty page is
+/// copied into `buf`.
+fn pick(round: usize, buf: &mut [u8]) -> Pick {
+    let mut frame_table = GlobalFrameTable::instance().lock();
+    let Some(index) = frame_table.used_pages.pop_front() else {
+        return Pick::Kept;
+    };
+    let pa = frame_table.start + (index << PG_SHIFT);
+    // Frames shared by forked processes stay until they are copied, and those
+    // with futex waiters until they are woken up.
+    if GlobalFrameTable::is_shared(pa) || futex::is_waited(pa) {
+        frame_table.used_pages.push_back(index);
+        return Pick::Kept;
+    }
+    // Forget those that unmapped the page since, or got another frame by a
+    // copy-on-write. The frame isn't shared, so at most one still maps it.
+    frame_table.entries[index].retain(|info| info.maps(pa) != Some(false));
+    let (thread, va) = match frame_table.entries[index].as_slice() {
+        // Already dropped, through a duplicate in the queue.
+        [] => return Pick::Kept,
+        [info] => match info.thread.upgrade() {
+            Some(thread) => (thread, info.va),
+            None => return Pick::Kept,
+        },
+        _ => {
             frame_table.used_pages.push_back(index);
-            continue;
+            return Pick::Kept;
+        }
+    };
+
+    // Waiting for them could deadlock, as their holder may be waiting for the
+    // frame table.
+    let (Some(pt), Some(mapping_table), Some(mut spt)) = (
+        thread.pagetable.as_ref().and_then(|pt| pt.try_lock()),
+        thread.mapping_table.try_lock(),
+        thread.supplementary_pagetable.try_lock(),
+    ) else {
+        frame_table.used_pages.push_back(index);
+        return Pick::Kept;
+    };
+
+    let pte = match pt.get_pte_mut(va) {
+        Some(pte) if pte.is_valid() && pte.pa().value() == pa => pte,
+        // The page has been unmapped since, or got another frame by a
+        // copy-on-write.
+        _ => {
+            frame_table.entries[index].clear();
+            return Pick::Kept;
         }
+    };
+    if !POLICY.lock().evict(index, pte, round) {
+        frame_table.used_pages.push_back(index);
+        return Pick::Kept;
+    }
+
+    // Another hart may still write to it, until it's shot down.
+    pte.set_invalid();
+    crate::smp::shootdown(va.floor(), PG_SIZE);
 
-        if !pte.is_valid() {
-            panic!("page not present when swapping out");
+    let pick = if let Some(sptinfo) = spt.list.iter().find(|x| x.contains(va)) {
+        let l = (va - sptinfo.va).floor();
+        let size = (sptinfo.memsize - l).min(PG_SIZE);
+        if pte.is_dirty() {
+            Pick::Dirty(Target::Swap(sptinfo.offset + l), size)
+        } else {
+            Pick::Clean
         }
+    } else if let Some(mapinfo) = mapping_table.list.iter().find(|x| x.contains(va)) {
+        let l = (va - mapinfo.va).floor();
+        if !pte.is_dirty() {
+            Pick::Clean
+        } else if mapinfo.shared {
+            // Only shared mappings are written back in place. They don't grow
+            // their files.
+            let target = Target::File {
+                file: mapinfo.file.as_ref().unwrap().reopen(),
+                offset: mapinfo.offset + l,
+                owner: thread.clone(),
+                va: va.floor(),
+                flags: mapinfo.flags,
+            };
+            Pick::Dirty(target, (mapinfo.filesize.max(l) - l).min(PG_SIZE))
+        } else {
+            let Ok(slot) = Swap::new_page() else {
+                // Out of swap, look for a page that needs none.
+                pte.set_valid();
+                frame_table.used_pages.push_back(index);
+                return Pick::Kept;
+            };
+            spt.list.push(MapInfo::new(
+                -1,
+                None,
+                slot,
+                va.floor(),
+                PG_SIZE,
+                PG_SIZE,
+                mapinfo.flags | PTEFlags::W | PTEFlags::R,
+            ));
+            Pick::Dirty(Target::Swap(slot), (mapinfo.memsize.max(l) - l).min(PG_SIZE))
+        }
+    } else {
+        pte.set_valid();
+        frame_table.used_pages.push_back(index);
+        return Pick::Kept;
+    };
+
+    let frame = pte.pa().into_va();
+    if let Pick::Dirty(_, size) = pick {
+        let page = unsafe { (frame as *const [u8; PG_SIZE]).as_ref().unwrap() };
+        buf[..size].copy_from_slice(&page[..size]);
+    }
+    unsafe { UserPool::dealloc_pages(frame as *mut _, 1) };
+    frame_table.entries[index].clear();
+    pick
+}
 
-        let mut mapping_table = thread.mapping_table.lock();
-        let mut spt = thread.supplementary_pagetable.lock();
-        if let Some((pos, _)) = spt
-            .list
-            .iter()
-            .enumerate()
-            .find(|(_, x) | x.contains(va)) {
-
-            let sptinfo = spt.list.get(pos).unwrap();
-            if pte.is_dirty() {
-                let l = (va - sptinfo.va).floor();
-                let size = (sptinfo.memsize - l).min(PG_SIZE);
-                let buf = unsafe {
-                    (pte.pa().into_va() as *const [u8; PG_SIZE])
-                        .as_ref()
-                        .unwrap()
-                };
-                mapping_table.release();
-                pt.release();
-                spt.release();
-                Swap::write_page(sptinfo.offset + l, &buf[..size]);
-                mapping_table.acquire();
-                pt.acquire();
-                spt.acquire();
-            }
-            unsafe {
-                UserPool::dealloc_pages(pte.pa().into_va() as *mut _, 1);
-            }
-            pte.set_invalid();
-        } else if let Some((pos, _)) = mapping_table
-            .list
-            .iter()
-            .enumerate()
-            .find(|(_, x)| x.contains(va)) {
-            let mapinfo = mapping_table.list.get_mut(pos).unwrap();
-            if pte.is_dirty() {
-                let l = (va - mapinfo.va).floor();
-                mapinfo
-                    .file
-                    .as_mut()
-                    .unwrap()
-                    .seek(SeekFrom::Start(mapinfo.offset + l))
-                    .unwrap();
-                let size = (mapinfo.memsize.max(l) - l).min(PG_SIZE);
-                let buf = unsafe {
-                    (pte.pa().into_va() as *const [u8; PG_SIZE])
-                        .as_ref()
-                        .unwrap()
-                };
-                if mapinfo.mapid == -1
-                    || mapinfo
-                        .file
-                        .as_mut()
-                        .unwrap()
-                        .write(&buf[..size])
-                        .is_err() {
-                    let swap_pos = Swap::new_page();
-                    let sptinfo = MapInfo::new(
-                        -1,
-                        None,
-                        swap_pos,
-                        va.floor(),
-                        PG_SIZE,
-                        PG_SIZE,
-                        mapinfo.flags | PTEFlags::W | PTEFlags::R,
-                    );
-
-                    mapping_table.release();
-                    pt.release();
-                    spt.release();
-                    Swap::write_page(swap_pos, &buf[..size]);
-                    mapping_table.acquire();
-                    pt.acquire();
-                    spt.acquire();
-
-                    spt.list.push(sptinfo);
-                }
-            }
-            unsafe {
-                UserPool::dealloc_pages((pte.pa().into_va()) as *mut _, 1);
+/// Writes out a page evicted by [`pick`], whose contents are `buf`.
+fn write_out(target: Target, buf: &[u8]) {
+    match target {
+        Target::Swap(pos) => {
+            Swap::write_page(pos, buf).expect("swap slot of a page out of range")
+        }
+        Target::File {
+            mut file,
+            offset,
+            owner,
+            va,
+            flags,
+        } => {
+            file.seek(SeekFrom::Start(offset)).unwrap();
+            if file.write(buf).is_ok() {
+                return;
             }
-            pte.set_invalid();
-        } else {
-            frame_table.used_pages.push_back(index);
-            continue;
+            // The file can't be written anymore, the page goes to the swap.
+            let Ok(slot) = Swap::new_page() else {
+                kprintln!("User thread {} killed: out of swap.", owner.name());
+                owner.kill();
+                return;
+            };
+            Swap::write_page(slot, buf).expect("new swap slot out of range");
+            let flags = flags | PTEFlags::W | PTEFlags::R;
+            let sptinfo = MapInfo::new(-1, None, slot, va, PG_SIZE, PG_SIZE, flags);
+            owner.supplementary_pagetable.lock().list.push(sptinfo);
         }
-        *entry = None;
-        return true;
     }
-    unreachable!("memory exhausted!");
-}
\ No newline at end of file
+}
@@ -1,11 +1,12 @@
 use alloc::collections::VecDeque;
+use alloc::vec::Vec;
 
 use crate::{mem::{PTEFlags, USER_POOL_LIMIT, VM_OFFSET}, trap::Frame};
 
 use core::array;
 use core::cmp::min;
 use alloc::sync::{Arc, Weak};
-use crate::sync::{Intr, Lazy, Mutex, Primitive};
+use crate::sync::{Lazy, Mutex, Spin};
 use crate::fs::disk::Swap;
 use crate::io::{Seek, SeekFrom, Write, Read};
 use crate::mem::utils::*;
@@ -21,12 +22,27 @@ impl FrameInfo {
     pub fn new(thread: Weak<Thread>, va: usize, flags: PTEFlags) -> Self {
         Self { thread, va, flags }
     }
+
+    /// Whether its thread still maps the frame at `pa`, or `None` if its page
+    /// table is in use on another hart.
+    pub fn maps(&self, pa: usize) -> Option<bool> {
+        let Some(thread) = self.thread.upgrade() else {
+            return Some(false);
+        };
+        let Some(pt) = thread.pagetable.as_ref() else {
+            return Some(false);
+        };
+        let pt = pt.try_lock()?;
+        Some(matches!(pt.get_pte(self.va), Some(pte) if pte.is_valid() && pte.pa().value() == pa))
+    }
 }
 
 pub struct FrameTable {
     pub start: usize,
     pub end: usize,
-    pub entries: [Option<FrameInfo>; USER_POOL_LIMIT],
+    /// Every address space that mapped each frame. Some may have dropped it
+    /// since, they are only weeded out when the frame is looked at.
+    pub entries: [Vec<FrameInfo>; USER_POOL_LIMIT],
     pub used_pages: VecDeque<usize>,
 }
 
@@ -35,7 +51,7 @@ impl FrameTable {
         Self {
             start: 0,
             end: 0,
-            entries: array::from_fn(|_| None),
+            entries: array::from_fn(|_| Vec::new()),
             used_pages: VecDeque::new(),
         }
     }
@@ -46,33 +62,95 @@ impl FrameTable {
     }
 }
 
-pub struct GlobalFrameTable(Lazy<Mutex<FrameTable, Primitive>>);
+/// Number of address spaces mapping each frame, the page cache counting as
+/// one. 0 and 1 both mean it's exclusive, only frames shared by a fork or
+/// cached count higher.
+///
+/// It's kept apart from the frame table, whose lock is held while a victim is
+/// picked, so that frames can be freed at any time.
+struct Refs {
+    start: usize,
+    counts: [usize; USER_POOL_LIMIT],
+}
+
+impl Refs {
+    fn count(&mut self, pa: usize) -> Option<&mut usize> {
+        let index = pa.checked_sub(self.start)? >> PG_SHIFT;
+        self.counts.get_mut(index)
+    }
+}
+
+static REFS: Lazy<Mutex<Refs, Spin>> = Lazy::new(|| {
+    Mutex::new(Refs {
+        start: usize::MAX,
+        counts: [0; USER_POOL_LIMIT],
+    })
+});
+
+pub struct GlobalFrameTable(Lazy<Mutex<FrameTable, Spin>>);
 
 impl GlobalFrameTable {
     pub fn init(start: usize, end: usize) {
         Self::instance().lock().set_range(start, end);
+        REFS.lock().start = start - VM_OFFSET;
     }
 
     pub fn map(pa: usize, va: usize, flag: PTEFlags) {
+        Self::map_for(&current(), pa, va, flag);
+    }
+
+    /// Records that `thread` maps the frame at `pa` to `va`, along with the
+    /// others that map it.
+    pub fn map_for(thread: &Arc<Thread>, pa: usize, va: usize, flag: PTEFlags) {
         let mut frame_table = Self::instance().lock();
 
         let index = (pa - frame_table.start) >> PG_SHIFT;
-        match frame_table.entries.get(index).unwrap() {
-            Some(x) => {
-                x.thread.upgrade();
-            }
-            _ => (),
-        };
+        let thread = Arc::downgrade(thread);
+        frame_table.entries[index]
+            .retain(|x| x.thread.strong_count() > 0 && !(x.thread.ptr_eq(&thread) && x.va == va));
+        frame_table.entries[index].push(FrameInfo::new(thread, va, flag));
         frame_table.used_pages.push_back(index);
-        *frame_table.entries.get_mut(index).unwrap() = 
-            Some(FrameInfo::new(Arc::downgrade(&current()), va, flag));
     }
 
+    /// Forgets that the current thread mapped the frame at `pa`, unless it
+    /// still does, e.g. through the image that replaced the old one.
     pub fn destroy(pa: usize) {
         let mut frame_table = Self::instance().lock();
 
-        let index = (pa - frame_table.start) >> PG_SHIFT;
-        *frame_table.entries.get_mut(index).unwrap() = None;
+        let Some(index) = pa.checked_sub(frame_table.start).map(|pa| pa >> PG_SHIFT) else {
+            return;
+        };
+        let current = Arc::downgrade(&current());
+        if let Some(entries) = frame_table.entries.get_mut(index) {
+            entries.retain(|x| !x.thread.ptr_eq(&current) || x.maps(pa) != Some(false));
+        }
+    }
+
+    /// Adds a mapping of the frame at `pa` by another address space.
+    pub fn share(pa: usize) {
+        if let Some(count) = REFS.lock().count(pa) {
+            *count = (*count).max(1) + 1;
+        }
+    }
+
+    /// Whether the frame at `pa` is mapped by more than one address space.
+    pub fn is_shared(pa: usize) -> bool {
+        REFS.lock().count(pa).is_some_and(|count| *count > 1)
+    }
+
+    /// Drops a mapping of the frame at `pa`. Returns whether it's unused now.
+    pub fn unshare(pa: usize) -> bool {
+        match REFS.lock().count(pa) {
+            Some(count) if *count > 1 => {
+                *count -= 1;
+                false
+            }
+            Some(count) => {
+                *count = 0;
+                true
+            }
+            None => true,
+        }
     }
 
     pub fn start() -> usize {
@@ -83,7 +161,7 @@ impl GlobalFrameTable {
         Self::instance().lock().end
     }
 
-    pub fn instance() -> &'static Mutex<FrameTable, Primitive> {
+    pub fn instance() -> &'static Mutex<FrameTable, Spin> {
         static FRAMETABLE: GlobalFrameTable = 
             GlobalFrameTable(Lazy::new(|| Mutex::new(FrameTable::new())));
 
@@ -0,0 +1,114 @@
+//! Page Replacement
+//!
+//! When the user pool runs out, a page is evicted to make room. Frames in use
+//! are kept in a circular queue, [`FrameTable::used_pages`](super::frame::FrameTable),
+//! and a clock hand goes over them, asking the policy whether to evict the one
+//! under it. A frame spared goes to the back of the queue. The hand may go
+//! around up to [`MAX_ROUNDS`] times, which every policy must be able to decide
+//! within. You can add new policies by implementing [`ReplacementPolicy`].
+//!
+//! Paging activity is counted in [`STATS`], so that policies can be compared.
+//! With the `mem-stats` feature, the counters are printed at shutdown.
+//!
+
+pub mod clock;
+pub mod enhanced;
+pub mod fifo;
+pub mod wsclock;
+
+use core::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+
+use crate::fs::disk::Swap;
+use crate::mem::pagecache::PageCache;
+use crate::mem::Entry;
+
+#[cfg(feature = "mem-replacement-fifo")]
+pub type Policy = self::fifo::Fifo;
+#[cfg(all(
+    feature = "mem-replacement-enhanced",
+    not(feature = "mem-replacement-fifo")
+))]
+pub type Policy = self::enhanced::EnhancedClock;
+#[cfg(all(
+    feature = "mem-replacement-wsclock",
+    not(any(
+        feature = "mem-replacement-fifo",
+        feature = "mem-replacement-enhanced"
+    ))
+))]
+pub type Policy = self::wsclock::WsClock;
+#[cfg(not(any(
+    feature = "mem-replacement-fifo",
+    feature = "mem-replacement-enhanced",
+    feature = "mem-replacement-wsclock"
+)))]
+pub type Policy = self::clock::Clock;
+
+/// Times the clock hand may go around all frames for one eviction.
+pub const MAX_ROUNDS: usize = 4;
+
+/// Basic functionalities of page replacement policies
+pub trait ReplacementPolicy: Default {
+    /// Name of the policy, reported along with the counters.
+    const NAME: &'static str;
+
+    /// Decides whether to evict the page in frame `index`, which `page` maps,
+    /// when the clock hand comes across it for the `round`-th time in this
+    /// eviction. To spare it, the policy may clear its accessed bit.
+    fn evict(&mut self, index: usize, page: &mut Entry, round: usize) -> bool;
+}
+
+/// Counters of paging activity
+pub struct Stats {
+    /// Pages faulted in, from swap or files, or newly allocated
+    pub faults: AtomicUsize,
+    /// Pages evicted
+    pub evictions: AtomicUsize,
+    /// Evicted pages written back, to the swap or their files
+    pub writebacks: AtomicUsize,
+    /// Pages read back from the swap
+    pub swapins: AtomicUsize,
+}
+
+pub static STATS: Stats = Stats {
+    faults: AtomicUsize::new(0),
+    evictions: AtomicUsize::new(0),
+    writebacks: AtomicUsize::new(0),
+    swapins: AtomicUsize::new(0),
+};
+
+impl Stats {
+    /// Adds one to `counter`.
+    pub fn count(counter: &AtomicUsize) {
+        counter.fetch_add(1, SeqCst);
+    }
+
+    /// Prints the counters, the usage of the swap and of the page cache.
+    pub fn dump(&self) {
+        kprintln!(
+            "[PAGING] policy: {}, faults: {}, evictions: {}, writebacks: {}, swap-ins: {}",
+            Policy::NAME,
+            self.faults.load(SeqCst),
+            self.evictions.load(SeqCst),
+            self.writebacks.load(SeqCst),
+            self.swapins.load(SeqCst),
+        );
+
+        let swap = Swap::stats();
+        kprintln!(
+            "[PAGING] swap slots: {}, used: {}, peak: {}, failed allocations: {}",
+            swap.slots,
+            swap.used,
+            swap.peak,
+            swap.failures,
+        );
+
+        let cache = PageCache::stats();
+        kprintln!(
+            "[PAGING] page cache: pages: {}, hits: {}, reads: {}",
+            cache.pages,
+            cache.hits,
+            cache.reads,
+        );
+    }
+}
@@ -0,0 +1,19 @@
+use crate::mem::palloc::replacement::ReplacementPolicy;
+use crate::mem::Entry;
+
+/// Second-chance clock. A page accessed since the hand last passed is spared
+/// once, and loses its accessed bit.
+#[derive(Default)]
+pub struct Clock;
+
+impl ReplacementPolicy for Clock {
+    const NAME: &'static str = "clock";
+
+    fn evict(&mut self, _index: usize, page: &mut Entry, _round: usize) -> bool {
+        if page.is_accessed() {
+            page.set_unaccessed();
+            return false;
+        }
+        true
+    }
+}
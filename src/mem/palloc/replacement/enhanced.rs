@@ -0,0 +1,26 @@
+use crate::mem::palloc::replacement::ReplacementPolicy;
+use crate::mem::Entry;
+
+/// Enhanced clock, which prefers clean pages as they need no write-back.
+///
+/// Pages fall into classes by their (accessed, dirty) bits. Even rounds look
+/// for (0, 0) and leave the bits alone. Odd rounds look for (0, 1), clearing
+/// the accessed bits on the way, so every page is in a class (0, _) by the
+/// fourth round.
+#[derive(Default)]
+pub struct EnhancedClock;
+
+impl ReplacementPolicy for EnhancedClock {
+    const NAME: &'static str = "enhanced-clock";
+
+    fn evict(&mut self, _index: usize, page: &mut Entry, round: usize) -> bool {
+        if round % 2 == 0 {
+            return !page.is_accessed() && !page.is_dirty();
+        }
+        if page.is_accessed() {
+            page.set_unaccessed();
+            return false;
+        }
+        true
+    }
+}
@@ -0,0 +1,14 @@
+use crate::mem::palloc::replacement::ReplacementPolicy;
+use crate::mem::Entry;
+
+/// Evicts the page mapped first, used or not.
+#[derive(Default)]
+pub struct Fifo;
+
+impl ReplacementPolicy for Fifo {
+    const NAME: &'static str = "fifo";
+
+    fn evict(&mut self, _index: usize, _page: &mut Entry, _round: usize) -> bool {
+        true
+    }
+}
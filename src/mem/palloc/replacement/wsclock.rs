@@ -0,0 +1,47 @@
+use crate::mem::palloc::replacement::ReplacementPolicy;
+use crate::mem::Entry;
+use crate::mem::USER_POOL_LIMIT;
+use crate::sbi::timer::{timer_ticks, TICKS_PER_SEC};
+
+/// Ticks since the last use, after which a page is out of the working set.
+const TAU: i64 = TICKS_PER_SEC as i64;
+
+/// WSClock. Pages are stamped with the time the hand last found them used, and
+/// those out of the working set are evicted, clean ones first.
+///
+/// Dirty pages aren't written back ahead of time: the first round only evicts
+/// clean pages out of the working set, the second any page out of it. If the
+/// working set takes up all memory, the next rounds fall back to clean pages,
+/// then to any page.
+pub struct WsClock {
+    last_used: [i64; USER_POOL_LIMIT],
+}
+
+impl Default for WsClock {
+    fn default() -> Self {
+        Self {
+            last_used: [0; USER_POOL_LIMIT],
+        }
+    }
+}
+
+impl ReplacementPolicy for WsClock {
+    const NAME: &'static str = "wsclock";
+
+    fn evict(&mut self, index: usize, page: &mut Entry, round: usize) -> bool {
+        let now = timer_ticks();
+        if page.is_accessed() {
+            page.set_unaccessed();
+            self.last_used[index] = now;
+            return false;
+        }
+
+        let age = now - self.last_used[index];
+        match round {
+            0 => age > TAU && !page.is_dirty(),
+            1 => age > TAU,
+            2 => !page.is_dirty(),
+            _ => true,
+        }
+    }
+}
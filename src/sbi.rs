@@ -34,6 +34,10 @@ macro_rules! call {
     };
 }
 
+// Extensions using `call!` must be declared after it.
+pub mod hsm;
+pub mod rfence;
+
 pub mod legacy {
     #![allow(dead_code)]
 
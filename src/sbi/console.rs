@@ -1,13 +1,21 @@
 use core::fmt::{Result, Write};
+use core::hint;
+use core::sync::atomic::{AtomicUsize, Ordering::SeqCst};
 
 use crate::sbi::{console_putchar, interrupt};
+use crate::smp;
+
+/// Hart holding the standard output, or `usize::MAX` if none
+static OWNER: AtomicUsize = AtomicUsize::new(usize::MAX);
+/// Times the owner has locked the standard output
+static DEPTH: AtomicUsize = AtomicUsize::new(0);
 
 pub struct Stdout;
 
 /// A locked standard output
 ///
-/// `StdoutLock` simply shuts down interrupt when acquired, and restores
-/// the previous interrupt setting when dropped.
+/// `StdoutLock` shuts down interrupt and keeps other harts from printing when
+/// acquired, and restores the previous interrupt setting when dropped.
 ///
 /// ## Examples
 /// ```
@@ -35,9 +43,21 @@ impl Stdout {
     ///
     /// This is a re-entrant lock, allowing called in a nested manner.
     pub fn lock(&self) -> StdoutLock {
+        let intr = interrupt::set(false);
+        let hart = smp::hart_id();
+        if OWNER.load(SeqCst) != hart {
+            while OWNER
+                .compare_exchange(usize::MAX, hart, SeqCst, SeqCst)
+                .is_err()
+            {
+                hint::spin_loop();
+            }
+        }
+        DEPTH.fetch_add(1, SeqCst);
+
         StdoutLock {
             inner: stdout(),
-            intr: interrupt::set(false),
+            intr,
         }
     }
 }
@@ -59,6 +79,9 @@ impl Write for StdoutLock<'_> {
 
 impl Drop for StdoutLock<'_> {
     fn drop(&mut self) {
+        if DEPTH.fetch_sub(1, SeqCst) == 1 {
+            OWNER.store(usize::MAX, SeqCst);
+        }
         interrupt::set(self.intr);
     }
 }
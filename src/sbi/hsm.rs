@@ -0,0 +1,31 @@
+//! Hart State Management Extension
+
+const EID: usize = 0x48534D;
+
+const HART_START: usize = 0;
+const HART_GET_STATUS: usize = 2;
+
+/// Hart states reported by [`hart_get_status`].
+pub const STARTED: usize = 0;
+pub const STOPPED: usize = 1;
+
+/// Starts hart `hart_id` in supervisor mode at physical address `start_addr`,
+/// with `a0` set to `hart_id` and `a1` set to `opaque`.
+///
+/// ## Return
+/// - `Ok(())`
+/// - `Err(error)`: an SBI error code, e.g. the hart is already started.
+pub fn hart_start(hart_id: usize, start_addr: usize, opaque: usize) -> Result<(), isize> {
+    match call!(EID, HART_START; hart_id, start_addr, opaque) {
+        (0, _) => Ok(()),
+        (err, _) => Err(err as isize),
+    }
+}
+
+/// Returns the state of hart `hart_id`, or an SBI error code.
+pub fn hart_get_status(hart_id: usize) -> Result<usize, isize> {
+    match call!(EID, HART_GET_STATUS; hart_id) {
+        (0, status) => Ok(status),
+        (err, _) => Err(err as isize),
+    }
+}
@@ -0,0 +1,11 @@
+//! Remote Fence Extension
+
+const EID: usize = 0x52464E43;
+
+const REMOTE_SFENCE_VMA: usize = 1;
+
+/// Executes `sfence.vma` on the harts selected by `hart_mask`, where bit `i`
+/// stands for hart `hart_mask_base + i`, covering `[start_addr, start_addr + size)`.
+pub fn remote_sfence_vma(hart_mask: usize, hart_mask_base: usize, start_addr: usize, size: usize) {
+    call!(EID, REMOTE_SFENCE_VMA; hart_mask, hart_mask_base, start_addr, size);
+}
@@ -1,8 +1,9 @@
 //! RISC-V Timer Interface
 
-use core::sync::atomic::{AtomicI64, Ordering::SeqCst};
+use core::sync::atomic::{AtomicI64, AtomicUsize, Ordering::SeqCst};
 
 use crate::sbi::set_timer;
+use crate::smp;
 
 pub const TICKS_PER_SEC: usize = 10;
 pub const CLOCK_PRE_SEC: usize = 12500000;
@@ -24,22 +25,60 @@ pub fn time_us() -> usize {
     clock() * 1_000_000 / CLOCK_PRE_SEC
 }
 
-/// Set the next moment when timer interrupt should happen
-#[inline]
+/// Clock readings in a tick
+const CLOCK_PER_TICK: usize = CLOCK_PRE_SEC / TICKS_PER_SEC;
+
+static TICKS: AtomicI64 = AtomicI64::new(0);
+/// Clock reading at which the boot hart counts the next tick
+static NEXT_TICK: AtomicUsize = AtomicUsize::new(0);
+
+/// Set the next moment when timer interrupt should happen. The boot hart
+/// follows the boundaries of ticks, so that it can tell how many ticks passed
+/// while its timer was off (see [`oneshot`]).
 pub fn next() {
-    set_timer(clock() + CLOCK_PRE_SEC / TICKS_PER_SEC);
+    if smp::hart_id() == smp::boot_hart() {
+        set_timer(next_tick());
+    } else {
+        set_timer(clock() + CLOCK_PER_TICK);
+    }
 }
 
-static TICKS: AtomicI64 = AtomicI64::new(0);
+/// Clock reading of the next tick, starting the count from now if it hasn't.
+fn next_tick() -> usize {
+    let _ = NEXT_TICK.compare_exchange(0, clock() + CLOCK_PER_TICK, SeqCst, SeqCst);
+    NEXT_TICK.load(SeqCst)
+}
+
+/// Set a single timer interrupt at the boundary of tick `wakeup`, instead of
+/// the ticks before it, or no interrupt at all if it's `None`. [`next`] brings
+/// back the periodic ticks. Only for the boot hart.
+pub fn oneshot(wakeup: Option<i64>) {
+    let deadline = match wakeup {
+        Some(wakeup) => {
+            let skipped = (wakeup - timer_ticks() - 1).max(0) as usize;
+            next_tick() + skipped * CLOCK_PER_TICK
+        }
+        None => usize::MAX,
+    };
+    set_timer(deadline);
+}
 
 /// Returns the number of timer ticks since booted.
 pub fn timer_ticks() -> i64 {
     TICKS.load(SeqCst)
 }
 
-/// Increments timer ticks by 1 and sets the next timer interrupt.
+/// Counts the ticks that have passed, usually one, letting the scheduler
+/// account for each of them, and sets the next timer interrupt. Only the boot
+/// hart counts ticks.
 pub fn tick() {
-    TICKS.fetch_add(1, SeqCst);
+    if smp::hart_id() == smp::boot_hart() {
+        while clock() >= next_tick() {
+            NEXT_TICK.fetch_add(CLOCK_PER_TICK, SeqCst);
+            TICKS.fetch_add(1, SeqCst);
+            crate::thread::Manager::get().tick();
+        }
+    }
     next();
 }
 
@@ -0,0 +1,233 @@
+//! Symmetric Multiprocessing
+//!
+//! The boot hart starts the other harts listed in the device tree through the
+//! SBI HSM extension. Every hart keeps a pointer to its own [`Local`] data in
+//! `tp`: kernel code never changes it, and trap entries from user mode restore
+//! it (see [`crate::trap`]).
+//!
+//! A secondary hart turns its boot stack into its idle thread, then schedules
+//! threads from the shared scheduler like the boot hart does. Timer ticks are
+//! only counted on the boot hart, while external interrupts are only routed to it.
+
+use core::arch::{asm, global_asm};
+use core::mem::{offset_of, size_of};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst};
+
+use fdt::Fdt;
+
+use crate::mem::{kalloc, KernelPgTable, VM_OFFSET};
+use crate::sbi::{hsm, interrupt, rfence};
+use crate::thread::{self, Manager, STACK_ALIGN, STACK_SIZE};
+use crate::trap;
+
+/// Harts with larger ids are left stopped.
+pub const MAX_HARTS: usize = 8;
+
+/// Data private to a hart, on a cache line of its own
+#[repr(C, align(64))]
+pub struct Local {
+    /// Id of the hart
+    id: usize,
+    /// Lowest address the kernel stack of the running thread may reach, which
+    /// is checked by `trap_entry_k`. Zero turns the check off.
+    stack_limit: AtomicUsize,
+    /// Scratch space of `trap_entry_k`
+    scratch: AtomicUsize,
+    /// Number of spin locks held on the hart
+    spins: AtomicUsize,
+    /// Interrupt status before the first of them was acquired
+    spin_intr: AtomicBool,
+}
+
+/// `size_of::<Local>()` as a power of two, for indexing in assembly
+pub const LOCAL_SHIFT: usize = size_of::<Local>().trailing_zeros() as usize;
+const _: () = assert!(1 << LOCAL_SHIFT == size_of::<Local>());
+pub const LOCAL_ID: usize = offset_of!(Local, id);
+pub const LOCAL_STACK_LIMIT: usize = offset_of!(Local, stack_limit);
+pub const LOCAL_SCRATCH: usize = offset_of!(Local, scratch);
+
+/// Data of all harts, indexed by hart id. Boot code points `tp` to an entry.
+#[no_mangle]
+static HART_LOCALS: [Local; MAX_HARTS] = {
+    let mut locals = [const {
+        Local {
+            id: 0,
+            stack_limit: AtomicUsize::new(0),
+            scratch: AtomicUsize::new(0),
+            spins: AtomicUsize::new(0),
+            spin_intr: AtomicBool::new(false),
+        }
+    }; MAX_HARTS];
+
+    let mut id = 0;
+    while id < MAX_HARTS {
+        locals[id].id = id;
+        id += 1;
+    }
+    locals
+};
+
+/// Number of harts running the kernel
+static ONLINE: AtomicUsize = AtomicUsize::new(1);
+/// Bitmask of the harts running the kernel
+static HARTS: AtomicUsize = AtomicUsize::new(0);
+/// Id of the hart that entered `main`
+static BOOT_HART: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the id of the current hart.
+///
+/// The caller should turn off interrupts if it cares about the answer staying
+/// true, as the current thread may be moved to another hart when preempted.
+#[inline]
+pub fn hart_id() -> usize {
+    local().id
+}
+
+/// Returns the data of the current hart.
+#[inline]
+fn local() -> &'static Local {
+    let tp: usize;
+    unsafe {
+        asm!("mv {}, tp", out(reg) tp);
+        &*(tp as *const Local)
+    }
+}
+
+/// Sets the lowest address the kernel stack may reach on the current hart.
+/// A trap that would push its frame below it is reported as a stack overflow.
+/// Must be called with interrupts off, whenever the hart changes its stack.
+pub fn set_stack_limit(limit: usize) {
+    local().stack_limit.store(limit, SeqCst);
+}
+
+/// Turns off interrupts on the current hart for a spin lock about to be
+/// acquired. The first one saves the previous interrupt status.
+pub fn push_off() {
+    let old = interrupt::set(false);
+    let local = local();
+    if local.spins.fetch_add(1, SeqCst) == 0 {
+        local.spin_intr.store(old, SeqCst);
+    }
+}
+
+/// Undoes a [`push_off`]. Once the last spin lock on the hart is released, the
+/// interrupt status saved by the first one is restored, no matter in which
+/// order they were released.
+pub fn pop_off() {
+    let local = local();
+    let spins = local.spins.fetch_sub(1, SeqCst);
+    assert!(spins > 0, "release before acquire");
+    if spins == 1 {
+        interrupt::set(local.spin_intr.load(SeqCst));
+    }
+}
+
+/// Returns the number of harts running the kernel.
+pub fn online() -> usize {
+    ONLINE.load(SeqCst)
+}
+
+/// Returns the id of the hart that booted the kernel.
+pub fn boot_hart() -> usize {
+    BOOT_HART.load(SeqCst)
+}
+
+/// Starts every other hart in the device tree, and returns once all of them
+/// are online. Must be called by the boot hart after the kernel is initialized.
+pub fn start_harts(devtree: &Fdt) {
+    BOOT_HART.store(hart_id(), SeqCst);
+    HARTS.fetch_or(1 << hart_id(), SeqCst);
+
+    // Initialize the manager before harts race for it.
+    Manager::get();
+
+    for cpu in devtree.cpus() {
+        let id = cpu.ids().first();
+        if id == hart_id() || id >= MAX_HARTS {
+            continue;
+        }
+
+        let stack = kalloc(STACK_SIZE, STACK_ALIGN) as usize;
+        let expected = online() + 1;
+        let entry = secondary_entry as usize - VM_OFFSET;
+        match hsm::hart_start(id, entry, stack + STACK_SIZE) {
+            Ok(()) => {
+                while online() < expected {
+                    core::hint::spin_loop();
+                }
+            }
+            Err(err) => kprintln!("[SMP] Failed to start hart {}: error {}", id, err),
+        }
+    }
+
+    #[cfg(feature = "debug")]
+    kprintln!("[SMP] {} harts online", online());
+}
+
+/// Flushes the TLB entries of `[va, va + size)` on every hart, typically after
+/// a page table entry that another hart may have cached is changed.
+pub fn shootdown(va: usize, size: usize) {
+    let local = hart_id();
+    unsafe { asm!("sfence.vma {}, zero", in(reg) va) };
+    if online() > 1 {
+        let others = HARTS.load(SeqCst) & !(1 << local);
+        rfence::remote_sfence_vma(others, 0, va, size);
+    }
+}
+
+extern "C" {
+    fn secondary_entry();
+}
+
+// Entry point of secondary harts, at a physical address with paging off.
+// `a0` is the hart id and `a1` the top of its boot stack.
+global_asm! {r#"
+    .section .text
+    .globl secondary_entry
+    secondary_entry:
+        la t0, entry_pgtable
+        srli t0, t0, 12
+        li t1, 0x8 << 60
+        or t0, t0, t1
+        sfence.vma zero, zero
+        csrw satp, t0
+        sfence.vma zero, zero
+
+        ld t0, _secondary_relocated
+        jr t0
+
+    secondary_relocated:
+        mv sp, a1
+
+        # tp = &HART_LOCALS[a0]
+        la tp, HART_LOCALS
+        slli t0, a0, {local_shift}
+        add tp, tp, t0
+
+        j secondary_main
+
+    _secondary_relocated:
+        .8byte secondary_relocated
+"#,
+    local_shift = const LOCAL_SHIFT,
+}
+
+#[no_mangle]
+extern "C" fn secondary_main(_hart_id: usize, stack_top: usize) -> ! {
+    KernelPgTable::get().activate();
+    trap::set_strap_entry();
+    unsafe { riscv::register::sstatus::set_sum() };
+
+    Manager::get().add_hart(stack_top - STACK_SIZE);
+    HARTS.fetch_or(1 << hart_id(), SeqCst);
+    ONLINE.fetch_add(1, SeqCst);
+
+    #[cfg(feature = "debug")]
+    kprintln!("[SMP] hart {} online", _hart_id);
+
+    crate::sbi::timer::next();
+    interrupt::set(true);
+    unsafe { riscv::register::sstatus::set_sie() };
+
+    thread::idle()
+}
@@ -0,0 +1,72 @@
+//! # Barrier
+//!
+//! A [`Barrier`] makes a group of `n` threads wait for each other: each one
+//! blocks in [`Barrier::wait`] until all `n` have called it, then all of them
+//! continue. It can be reused for the next round right away.
+//!
+//! ## Usage
+//!
+//! ```rust
+//! let barrier = Arc::new(Barrier::new(2));
+//!
+//! let b = barrier.clone();
+//! thread::spawn("worker", move || {
+//!     // Phase 1 ...
+//!     b.wait();
+//!     // Phase 2 ...
+//! });
+//!
+//! // Phase 1 ...
+//! barrier.wait(); // Both have finished phase 1 after this line.
+//! ```
+
+use crate::sync::{Condvar, Mutex};
+
+struct State {
+    /// Threads that have arrived in the current round
+    arrived: usize,
+    /// Number of rounds completed
+    generation: usize,
+}
+
+pub struct Barrier {
+    n: usize,
+    state: Mutex<State>,
+    cvar: Condvar,
+}
+
+impl Barrier {
+    /// Creates a barrier for a group of `n` threads.
+    #[track_caller]
+    pub fn new(n: usize) -> Self {
+        Self {
+            n,
+            state: Mutex::new(State {
+                arrived: 0,
+                generation: 0,
+            }),
+            cvar: Condvar::new(),
+        }
+    }
+
+    /// Blocks until all `n` threads of the group have called it. Returns
+    /// `true` for exactly one of them in each round, the last to arrive.
+    pub fn wait(&self) -> bool {
+        let mut state = self.state.lock();
+        state.arrived += 1;
+
+        if state.arrived >= self.n {
+            state.arrived = 0;
+            state.generation += 1;
+            self.cvar.notify_all();
+            return true;
+        }
+
+        // Threads of the next round must not let us through.
+        let generation = state.generation;
+        while generation == state.generation {
+            self.cvar.wait(&mut state);
+        }
+        false
+    }
+}
@@ -0,0 +1,155 @@
+//! # Bounded Channel
+//!
+//! [`channel`] creates a queue of bounded capacity, through which any number
+//! of [`Sender`]s pass values to a single [`Receiver`].
+//!
+//! Sending to a full channel blocks until the receiver makes room, and
+//! receiving from an empty one blocks until a value arrives. When several
+//! threads are blocked on the same side, the one of the highest priority is
+//! woken up first.
+//!
+//! Dropping all senders closes the channel: the receiver gets the values left,
+//! then `None`. Dropping the receiver makes sending fail, giving the value back.
+//!
+//! ## Usage
+//!
+//! ```rust
+//! let (tx, rx) = channel(2);
+//!
+//! let tx2 = tx.clone();
+//! thread::spawn("producer", move || tx2.send(1).unwrap());
+//! tx.send(2).unwrap();
+//! drop(tx);
+//!
+//! let mut sum = 0;
+//! while let Some(value) = rx.recv() {
+//!     sum += value;
+//! }
+//! assert_eq!(sum, 3);
+//! ```
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+use crate::sync::{Condvar, Mutex};
+
+struct State<T> {
+    queue: VecDeque<T>,
+    /// Number of senders alive
+    senders: usize,
+    /// Whether the receiver is alive
+    receiver: bool,
+}
+
+struct Chan<T> {
+    capacity: usize,
+    state: Mutex<State<T>>,
+    /// Notified when a value is sent or the last sender is gone
+    not_empty: Condvar,
+    /// Notified when a value is received or the receiver is gone
+    not_full: Condvar,
+}
+
+/// Creates a channel that holds at most `capacity` values.
+#[track_caller]
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "a channel must hold at least one value");
+
+    let chan = Arc::new(Chan {
+        capacity,
+        state: Mutex::new(State {
+            queue: VecDeque::with_capacity(capacity),
+            senders: 1,
+            receiver: true,
+        }),
+        not_empty: Condvar::new(),
+        not_full: Condvar::new(),
+    });
+    (Sender(chan.clone()), Receiver(chan))
+}
+
+/// The sending side of a [`channel`], which can be cloned.
+pub struct Sender<T>(Arc<Chan<T>>);
+
+impl<T> Sender<T> {
+    /// Sends `value`, blocking while the channel is full. Fails, giving the
+    /// value back, if the receiver is gone.
+    pub fn send(&self, value: T) -> Result<(), T> {
+        let mut state = self.0.state.lock();
+        while state.receiver && state.queue.len() == self.0.capacity {
+            self.0.not_full.wait(&mut state);
+        }
+        self.push(&mut state, value)
+    }
+
+    /// Sends `value` without blocking. Fails, giving the value back, if the
+    /// channel is full or the receiver is gone.
+    pub fn try_send(&self, value: T) -> Result<(), T> {
+        let mut state = self.0.state.lock();
+        if state.queue.len() == self.0.capacity {
+            return Err(value);
+        }
+        self.push(&mut state, value)
+    }
+
+    fn push(&self, state: &mut State<T>, value: T) -> Result<(), T> {
+        if !state.receiver {
+            return Err(value);
+        }
+        state.queue.push_back(value);
+        self.0.not_empty.notify_one();
+        Ok(())
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.0.state.lock().senders += 1;
+        Sender(self.0.clone())
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            self.0.not_empty.notify_all();
+        }
+    }
+}
+
+/// The receiving side of a [`channel`].
+pub struct Receiver<T>(Arc<Chan<T>>);
+
+impl<T> Receiver<T> {
+    /// Receives a value, blocking while the channel is empty. Returns `None`
+    /// once it's empty and all senders are gone.
+    pub fn recv(&self) -> Option<T> {
+        let mut state = self.0.state.lock();
+        while state.queue.is_empty() && state.senders > 0 {
+            self.0.not_empty.wait(&mut state);
+        }
+        self.pop(&mut state)
+    }
+
+    /// Receives a value without blocking, or returns `None` if the channel is
+    /// empty.
+    pub fn try_recv(&self) -> Option<T> {
+        let mut state = self.0.state.lock();
+        self.pop(&mut state)
+    }
+
+    fn pop(&self, state: &mut State<T>) -> Option<T> {
+        let value = state.queue.pop_front()?;
+        self.0.not_full.notify_one();
+        Some(value)
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.0.state.lock().receiver = false;
+        self.0.not_full.notify_all();
+    }
+}
@@ -21,6 +21,9 @@
 //! }
 //! ```
 //!
+//! To give up after a while, use [`Condvar::wait_timeout`] instead, which tells
+//! whether it timed out.
+//!
 //! Here is a good practice of thread B:
 //! ```rust
 //! let (lock, cvar) = &*pair;
@@ -47,8 +50,10 @@ use alloc::sync::Arc;
 use core::cell::RefCell;
 
 use crate::sync::{Lock, MutexGuard, Semaphore};
+use crate::thread::scheduler::first_max;
+use crate::thread::{self, Thread};
 
-pub struct Condvar(RefCell<VecDeque<Arc<Semaphore>>>);
+pub struct Condvar(RefCell<VecDeque<(Arc<Thread>, Arc<Semaphore>)>>);
 
 unsafe impl Sync for Condvar {}
 unsafe impl Send for Condvar {}
@@ -60,23 +65,56 @@ impl Condvar {
 
     pub fn wait<T, L: Lock>(&self, guard: &mut MutexGuard<'_, T, L>) {
         let sema = Arc::new(Semaphore::new(0));
-        self.0.borrow_mut().push_front(sema.clone());
+        self.0
+            .borrow_mut()
+            .push_front((thread::current(), sema.clone()));
 
         guard.release();
         sema.down();
         guard.acquire();
     }
 
-    /// Wake up one thread from the waiting list
+    /// Like [`wait`](Self::wait), but gives up after `ticks` timer ticks.
+    /// Returns whether it timed out without being notified.
+    pub fn wait_timeout<T, L: Lock>(&self, guard: &mut MutexGuard<'_, T, L>, ticks: i64) -> bool {
+        let sema = Arc::new(Semaphore::new(0));
+        self.0
+            .borrow_mut()
+            .push_front((thread::current(), sema.clone()));
+
+        guard.release();
+        let timed_out = sema.down_timeout(ticks);
+        guard.acquire();
+
+        // A notification may have come between the timeout and `acquire`, in
+        // which case it's ours, or it would be lost.
+        let mut waiters = self.0.borrow_mut();
+        let count = waiters.len();
+        waiters.retain(|(_, s)| !Arc::ptr_eq(s, &sema));
+        timed_out && waiters.len() < count
+    }
+
+    /// Wake up the waiting thread of the highest priority
     pub fn notify_one(&self) {
-        if let Some(sema) = self.0.borrow_mut().pop_back() {
+        let mut waiters = self.0.borrow_mut();
+        // The oldest waiters are at the back.
+        let index = first_max(
+            waiters
+                .iter()
+                .enumerate()
+                .rev()
+                .map(|(i, (t, _))| (i, t.effective_priority())),
+        );
+
+        if let Some((_, sema)) = index.and_then(|i| waiters.remove(i)) {
+            drop(waiters);
             sema.up();
         }
     }
 
     /// Wake up all waiting threads
     pub fn notify_all(&self) {
-        self.0.borrow().iter().for_each(|s| s.up());
-        self.0.borrow_mut().clear();
+        let waiters: VecDeque<_> = self.0.borrow_mut().drain(..).collect();
+        waiters.iter().for_each(|(_, s)| s.up());
     }
 }
@@ -0,0 +1,249 @@
+//! # Lock Dependency Validator
+//!
+//! With the `lockdep` feature, every [`Mutex`](super::Mutex) belongs to a lock
+//! class, which is where it's created. Each thread's held locks are tracked,
+//! and acquiring a lock of class `B` while holding one of class `A` records
+//! the dependency `A -> B`, along with the chain of locks held at that time.
+//!
+//! The kernel panics, printing both chains, as soon as a dependency would close
+//! a cycle, i.e. the two orders that could deadlock have been seen, even if they
+//! never ran at the same time. It also panics when a sleep lock is acquired
+//! with interrupts off, be it by a held lock or by `sbi::interrupt::set`.
+//!
+//! Locks of the same class nested in each other, such as the locks of two
+//! threads, are not checked against each other. Nor is acquiring a lock with
+//! [`Mutex::try_lock`](super::Mutex::try_lock), which never waits.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::cmp::Ordering;
+use core::fmt::{self, Write};
+use core::panic::Location;
+use core::sync::atomic::{AtomicBool, Ordering::SeqCst};
+
+use crate::sbi::interrupt;
+use crate::smp::{hart_id, MAX_HARTS};
+use crate::sync::{Lock, Spin};
+use crate::thread;
+
+/// A lock class, named after where its locks are created.
+#[derive(Debug, Clone, Copy)]
+pub struct Class(&'static Location<'static>);
+
+impl Class {
+    #[track_caller]
+    pub fn caller() -> Self {
+        Self(Location::caller())
+    }
+
+    fn key(&self) -> usize {
+        self.0 as *const _ as usize
+    }
+}
+
+impl PartialEq for Class {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Class {}
+
+impl PartialOrd for Class {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Class {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "lock created at {}", self.0)
+    }
+}
+
+/// A lock held by a thread
+#[derive(Debug, Clone, Copy)]
+struct Held {
+    class: Class,
+    /// Where it was acquired
+    site: &'static Location<'static>,
+    sleeps: bool,
+}
+
+#[derive(Default)]
+struct Graph {
+    /// Locks held by each thread, in acquisition order
+    held: BTreeMap<isize, Vec<Held>>,
+    /// `deps[a][b]` is the chain that made `a -> b`, ending with the lock of `b`.
+    deps: BTreeMap<Class, BTreeMap<Class, Vec<Held>>>,
+}
+
+impl Graph {
+    /// Classes from `from` to `to` along the dependencies, both included.
+    fn path(&self, from: Class, to: Class) -> Option<Vec<Class>> {
+        let mut stack = Vec::from([Vec::from([from])]);
+        let mut visited = Vec::from([from]);
+
+        while let Some(path) = stack.pop() {
+            let last = *path.last().unwrap();
+            if last == to {
+                return Some(path);
+            }
+            for next in self.deps.get(&last).into_iter().flat_map(|d| d.keys()) {
+                if !visited.contains(next) {
+                    visited.push(*next);
+                    let mut path = path.clone();
+                    path.push(*next);
+                    stack.push(path);
+                }
+            }
+        }
+        None
+    }
+}
+
+struct State {
+    lock: Spin,
+    graph: UnsafeCell<Option<Graph>>,
+}
+
+unsafe impl Sync for State {}
+
+static STATE: State = State {
+    lock: Spin::new(),
+    graph: UnsafeCell::new(None),
+};
+
+/// Checking starts once threads are up, see [`enable`].
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether a hart is inside the validator, whose own locks are not checked.
+static BUSY: [AtomicBool; MAX_HARTS] = [const { AtomicBool::new(false) }; MAX_HARTS];
+
+/// Starts checking. Must be called after the thread manager works.
+pub fn enable() {
+    ENABLED.store(true, SeqCst);
+}
+
+/// Runs `f` on the graph with the current thread's id, unless the validator
+/// is off or already running on this hart.
+fn with_graph<R>(f: impl FnOnce(&mut Graph, isize) -> R) -> Option<R> {
+    if !ENABLED.load(SeqCst) {
+        return None;
+    }
+
+    let old = interrupt::set(false);
+    let busy = &BUSY[hart_id()];
+    if busy.swap(true, SeqCst) {
+        interrupt::set(old);
+        return None;
+    }
+
+    let tid = thread::current().id();
+    STATE.lock.acquire();
+    let graph = unsafe { (*STATE.graph.get()).get_or_insert_with(Graph::default) };
+    let result = f(graph, tid);
+    STATE.lock.release();
+
+    busy.store(false, SeqCst);
+    interrupt::set(old);
+    Some(result)
+}
+
+/// Checks and records that the current thread is about to acquire a lock of
+/// `class` at `site`. `sleeps` tells whether it's a sleep lock.
+pub fn acquire(class: Class, site: &'static Location<'static>, sleeps: bool) {
+    let new = Held { class, site, sleeps };
+    // Before the validator turns them off itself.
+    let intr_off = sleeps && !interrupt::get();
+    let report = with_graph(|graph, tid| {
+        let held = graph.held.entry(tid).or_default();
+        let mut chain = held.clone();
+        chain.push(new);
+
+        if intr_off || (sleeps && held.iter().any(|h| !h.sleeps)) {
+            let mut msg = String::from("lockdep: sleep lock acquired with interrupts off\n");
+            write_chain(&mut msg, tid, &chain);
+            return Some(msg);
+        }
+
+        for h in chain.iter().rev().skip(1).filter(|h| h.class != class) {
+            if let Some(path) = graph.path(class, h.class) {
+                let mut msg = String::from("lockdep: possible circular locking\n");
+                write_chain(&mut msg, tid, &chain);
+                let _ = writeln!(msg, "while the reverse order was seen before:");
+                for edge in path.windows(2) {
+                    write_chain(&mut msg, -1, &graph.deps[&edge[0]][&edge[1]]);
+                }
+                return Some(msg);
+            }
+        }
+
+        for h in chain.iter().rev().skip(1).filter(|h| h.class != class) {
+            graph
+                .deps
+                .entry(h.class)
+                .or_default()
+                .entry(class)
+                .or_insert_with(|| chain.clone());
+        }
+        graph.held.get_mut(&tid).unwrap().push(new);
+        None
+    });
+
+    if let Some(msg) = report.flatten() {
+        panic!("{}", msg);
+    }
+}
+
+/// Records that the current thread acquired a spin lock of `class` at `site`
+/// without waiting for it. That can't deadlock, so nothing is checked.
+pub fn try_acquire(class: Class, site: &'static Location<'static>) {
+    let new = Held {
+        class,
+        site,
+        sleeps: false,
+    };
+    with_graph(|graph, tid| graph.held.entry(tid).or_default().push(new));
+}
+
+/// Records that the current thread released a lock of `class`.
+pub fn release(class: Class) {
+    with_graph(|graph, tid| {
+        if let Some(held) = graph.held.get_mut(&tid) {
+            if let Some(i) = held.iter().rposition(|h| h.class == class) {
+                held.remove(i);
+            }
+            if held.is_empty() {
+                graph.held.remove(&tid);
+            }
+        }
+    });
+}
+
+/// Forgets the locks of an exited thread.
+pub fn forget(tid: isize) {
+    with_graph(|graph, _| graph.held.remove(&tid));
+}
+
+fn write_chain(msg: &mut String, tid: isize, chain: &[Held]) {
+    match tid {
+        -1 => {
+            let _ = writeln!(msg, "  chain:");
+        }
+        tid => {
+            let _ = writeln!(msg, "  thread {} acquiring:", tid);
+        }
+    }
+    for (i, h) in chain.iter().enumerate() {
+        let _ = writeln!(msg, "    #{} {}, acquired at {}", i, h.class, h.site);
+    }
+}
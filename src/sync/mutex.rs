@@ -1,7 +1,9 @@
 use core::cell::UnsafeCell;
 use core::ops::{Deref, DerefMut};
 
-use crate::sync::{self, Lock};
+#[cfg(feature = "lockdep")]
+use crate::sync::lockdep;
+use crate::sync::{self, Lock, Spin};
 
 /// A mutual exclusion primitive useful for protecting shared data
 ///
@@ -14,10 +16,12 @@ use crate::sync::{self, Lock};
 /// }
 /// assert_eq!(foo.lock(), 10);
 /// ```
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct Mutex<T, L: Lock = sync::Primitive> {
     value: UnsafeCell<T>,
     lock: L,
+    #[cfg(feature = "lockdep")]
+    class: lockdep::Class,
 }
 
 // The only access to a Mutex's value is MutexGuard, so safety is guaranteed here.
@@ -27,20 +31,52 @@ unsafe impl<T: Send, L: Lock> Send for Mutex<T, L> {}
 
 impl<T, L: Lock> Mutex<T, L> {
     /// Creates a mutex in an unlocked state ready for use.
+    ///
+    /// With `lockdep`, where it's called decides the lock class.
+    #[track_caller]
     pub fn new(value: T) -> Self {
         Self {
             value: UnsafeCell::new(value),
             lock: L::default(),
+            #[cfg(feature = "lockdep")]
+            class: lockdep::Class::caller(),
         }
     }
 
     /// Acquires a mutex, blocking the current thread until it is able to do so.
+    #[track_caller]
     pub fn lock(&self) -> MutexGuard<'_, T, L> {
+        #[cfg(feature = "lockdep")]
+        lockdep::acquire(self.class, core::panic::Location::caller(), L::SLEEPS);
+
         self.lock.acquire();
         MutexGuard(self)
     }
 }
 
+impl<T> Mutex<T, Spin> {
+    /// Acquires a mutex if it's free, or returns `None` right away. It never
+    /// waits, so it can't deadlock with the holder.
+    #[track_caller]
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T, Spin>> {
+        if !self.lock.try_acquire() {
+            return None;
+        }
+
+        #[cfg(feature = "lockdep")]
+        lockdep::try_acquire(self.class, core::panic::Location::caller());
+
+        Some(MutexGuard(self))
+    }
+}
+
+impl<T: Default, L: Lock> Default for Mutex<T, L> {
+    #[track_caller]
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
 /// An RAII implementation of a “scoped lock” of a mutex.
 /// When this structure is dropped (falls out of scope), the lock will be unlocked.
 ///
@@ -66,7 +102,7 @@ impl<T, L: Lock> DerefMut for MutexGuard<'_, T, L> {
 
 impl<T, L: Lock> Drop for MutexGuard<'_, T, L> {
     fn drop(&mut self) {
-        self.0.lock.release();
+        self.release();
     }
 }
 
@@ -74,9 +110,16 @@ impl<T, L: Lock> Drop for MutexGuard<'_, T, L> {
 impl<T, L: Lock> MutexGuard<'_, T, L> {
     pub fn release(&self) {
         self.0.lock.release();
+
+        #[cfg(feature = "lockdep")]
+        lockdep::release(self.0.class);
     }
 
+    #[track_caller]
     pub fn acquire(&self) {
+        #[cfg(feature = "lockdep")]
+        lockdep::acquire(self.0.class, core::panic::Location::caller(), L::SLEEPS);
+
         self.0.lock.acquire();
     }
 }
@@ -1,6 +1,6 @@
 use core::cell::Cell;
 
-use super::{Intr, Lock};
+use super::{Lock, Spin};
 
 #[derive(Clone, Copy)]
 pub enum OnceState {
@@ -12,7 +12,7 @@ pub enum OnceState {
 /// used to run a one-time global initialization.
 pub struct Once {
     inner: Cell<OnceState>,
-    lock: Intr,
+    lock: Spin,
 }
 
 unsafe impl Sync for Once {}
@@ -21,7 +21,7 @@ impl Once {
     pub const fn new() -> Self {
         Self {
             inner: Cell::new(OnceState::InComplete),
-            lock: Intr::new(),
+            lock: Spin::new(),
         }
     }
 
@@ -0,0 +1,201 @@
+//! # Reader-Writer Lock
+//!
+//! [`RwLock`] allows any number of readers, or a single writer, to access the
+//! data at the same time. It suits data that is read far more often than it
+//! is written.
+//!
+//! A writer is preferred over readers: once a writer is waiting, new readers
+//! wait behind it, so that writers are never starved. When the lock becomes
+//! free, it is handed over to the waiting writer of the highest priority, or
+//! if there is none, to all waiting readers at once.
+//!
+//! ## Usage
+//!
+//! ```rust
+//! let lock = RwLock::new(5);
+//!
+//! {
+//!     let r1 = lock.read();
+//!     let r2 = lock.read(); // Many readers at the same time
+//!     assert_eq!(*r1 + *r2, 10);
+//! }
+//!
+//! *lock.write() += 1; // Only one writer
+//! assert_eq!(*lock.read(), 6);
+//! ```
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::Ordering::SeqCst;
+
+use crate::sync::{Mutex, Semaphore, Spin};
+use crate::thread::scheduler::first_max;
+use crate::thread::{self, Thread};
+
+/// A thread waiting for the lock, which it is handed over through `sema`.
+struct Waiter {
+    thread: Arc<Thread>,
+    write: bool,
+    sema: Arc<Semaphore>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Number of readers holding the lock
+    readers: usize,
+    /// Whether a writer holds the lock
+    writer: bool,
+    /// Waiting threads in arrival order
+    waiters: Vec<Waiter>,
+}
+
+impl State {
+    fn writer_waiting(&self) -> bool {
+        self.waiters.iter().any(|w| w.write)
+    }
+
+    /// Hands the free lock over to the waiting writer of the highest priority,
+    /// or else to all waiting readers. Returns the semaphores to raise.
+    fn hand_over(&mut self) -> Vec<Arc<Semaphore>> {
+        let writer = first_max(
+            self.waiters
+                .iter()
+                .enumerate()
+                .filter(|(_, w)| w.write)
+                .map(|(i, w)| (i, w.thread.effective_priority())),
+        );
+
+        match writer {
+            Some(i) => {
+                self.writer = true;
+                Vec::from([self.waiters.remove(i).sema])
+            }
+            None => {
+                self.readers += self.waiters.len();
+                self.waiters.drain(..).map(|w| w.sema).collect()
+            }
+        }
+    }
+}
+
+/// A reader-writer lock, see the [module documentation](self).
+pub struct RwLock<T> {
+    value: UnsafeCell<T>,
+    state: Mutex<State, Spin>,
+}
+
+// Readers on different threads share `&T`, so T must be Sync as well.
+unsafe impl<T: Send + Sync> Sync for RwLock<T> {}
+unsafe impl<T: Send> Send for RwLock<T> {}
+
+impl<T> RwLock<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            value: UnsafeCell::new(value),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Acquires shared access, blocking while a writer holds or waits for the lock.
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.acquire(false);
+        RwLockReadGuard(self)
+    }
+
+    /// Acquires exclusive access, blocking while anyone else holds the lock.
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.acquire(true);
+        RwLockWriteGuard(self)
+    }
+
+    fn acquire(&self, write: bool) {
+        let current = thread::current();
+        // Like a sleep lock, dying while waiting for it or holding it is unsafe.
+        current.locks_held.fetch_add(1, SeqCst);
+
+        let sema = {
+            let mut state = self.state.lock();
+            if write && !state.writer && state.readers == 0 {
+                state.writer = true;
+                return;
+            }
+            if !write && !state.writer && !state.writer_waiting() {
+                state.readers += 1;
+                return;
+            }
+
+            let sema = Arc::new(Semaphore::new(0));
+            state.waiters.push(Waiter {
+                thread: current,
+                write,
+                sema: sema.clone(),
+            });
+            sema
+        };
+
+        // The lock is ours once we are woken up.
+        sema.down();
+    }
+
+    fn release(&self, write: bool) {
+        let semas = {
+            let mut state = self.state.lock();
+            if write {
+                state.writer = false;
+            } else {
+                state.readers -= 1;
+            }
+
+            if state.readers == 0 && !state.writer {
+                state.hand_over()
+            } else {
+                Vec::new()
+            }
+        };
+        semas.iter().for_each(|sema| sema.up());
+
+        thread::current().locks_held.fetch_sub(1, SeqCst);
+    }
+}
+
+/// Shared access to the data of an [`RwLock`], released when dropped.
+pub struct RwLockReadGuard<'a, T>(&'a RwLock<T>);
+
+impl<T> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.0.value.get() }
+    }
+}
+
+impl<T> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.0.release(false);
+    }
+}
+
+/// Exclusive access to the data of an [`RwLock`], released when dropped.
+pub struct RwLockWriteGuard<'a, T>(&'a RwLock<T>);
+
+impl<T> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.0.value.get() }
+    }
+}
+
+impl<T> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.0.value.get() }
+    }
+}
+
+impl<T> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.0.release(true);
+    }
+}
@@ -1,22 +1,30 @@
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
 use core::cell::{Cell, RefCell};
+use core::sync::atomic::Ordering::SeqCst;
 
 use crate::sbi;
-use crate::thread::{self, Thread};
+use crate::sbi::timer::timer_ticks;
+use crate::sync::{Lock, Spin};
+use crate::thread::scheduler::first_max;
+use crate::thread::{self, Manager, Status, Thread};
+use crate::userproc;
 
 /// Atomic counting semaphore
 ///
 /// # Examples
 /// ```
 /// let sema = Semaphore::new(0);
-/// sema.down();
 /// sema.up();
+/// sema.down();
+/// assert!(sema.down_timeout(10)); // Timed out
 /// ```
 #[derive(Clone)]
 pub struct Semaphore {
     value: Cell<usize>,
     waiters: RefCell<VecDeque<Arc<Thread>>>,
+    /// Guards the fields above against other harts
+    lock: Spin,
 }
 
 unsafe impl Sync for Semaphore {}
@@ -28,41 +36,119 @@ impl Semaphore {
         Semaphore {
             value: Cell::new(n),
             waiters: RefCell::new(VecDeque::new()),
+            lock: Spin::new(),
         }
     }
 
     /// P operation
     pub fn down(&self) {
+        self.down_until(None);
+    }
+
+    /// P operation that gives up after `ticks` timer ticks. Returns whether it
+    /// timed out, in which case the value is left untouched.
+    pub fn down_timeout(&self, ticks: i64) -> bool {
+        !self.down_until(Some(timer_ticks() + ticks))
+    }
+
+    /// Returns whether the semaphore was taken before tick `deadline`.
+    fn down_until(&self, deadline: Option<i64>) -> bool {
         let old = sbi::interrupt::set(false);
+        self.lock.acquire();
 
         // Is semaphore available?
         while self.value() == 0 {
-            // `push_front` ensures to wake up threads in a fifo manner
-            self.waiters.borrow_mut().push_front(thread::current());
+            if deadline.is_some_and(|deadline| timer_ticks() >= deadline) {
+                self.lock.release();
+                sbi::interrupt::set(old);
+                return false;
+            }
 
-            // Block the current thread until it's awakened by an `up` operation
-            thread::block();
+            // `push_front` ensures to wake up threads of equal priority in a fifo manner
+            let current = thread::current();
+            self.waiters.borrow_mut().push_front(current.clone());
+
+            // Block the current thread until it's awakened by an `up` operation,
+            // by a kill or by the timeout. It must be blocked before any of them
+            // can find it.
+            current.set_status(Status::Blocked);
+            current.interruptible.store(true, SeqCst);
+            if let Some(deadline) = deadline {
+                Manager::get().add_timeout(current.clone(), deadline);
+            }
+            drop(current);
+            self.lock.release();
+            thread::schedule();
+            self.lock.acquire();
+
+            let current = thread::current();
+            if deadline.is_some() || current.is_killed() {
+                // Not woken up by an `up`, we may still be waiting.
+                self.waiters
+                    .borrow_mut()
+                    .retain(|t| !Arc::ptr_eq(t, &current));
+            }
+            if deadline.is_some() {
+                Manager::get().cancel_timeout(&current);
+            }
+
+            if current.is_killed()
+                && current.userproc.is_some()
+                && current.locks_held.load(SeqCst) == 0
+            {
+                drop(current);
+                self.lock.release();
+                // Exiting takes sleep locks.
+                sbi::interrupt::set(old);
+                userproc::exit_killed();
+            }
         }
         self.value.set(self.value() - 1);
 
+        self.lock.release();
         sbi::interrupt::set(old);
+        true
     }
 
     /// V operation
     pub fn up(&self) {
         let old = sbi::interrupt::set(false);
-        let count = self.value.replace(self.value() + 1);
+        self.lock.acquire();
+        self.value.set(self.value() + 1);
 
-        // Check if we need to wake up a sleeping waiter
-        if let Some(thread) = self.waiters.borrow_mut().pop_back() {
-            assert_eq!(count, 0);
+        // Check if we need to wake up a sleeping waiter. Waiters woken up by a
+        // kill or a timeout already are dropped, they don't take the value.
+        let waiter = loop {
+            match self.highest_waiter() {
+                Some(thread) if !thread.interruptible.swap(false, SeqCst) => continue,
+                waiter => break waiter,
+            }
+        };
+        self.lock.release();
 
+        if let Some(thread) = waiter {
             thread::wake_up(thread.clone());
+            thread::preempt(&thread);
         }
 
         sbi::interrupt::set(old);
     }
 
+    /// Remove the waiter of the highest effective priority. Among waiters of
+    /// equal priority, the one that has waited the longest is chosen.
+    fn highest_waiter(&self) -> Option<Arc<Thread>> {
+        let mut waiters = self.waiters.borrow_mut();
+        // The oldest waiters are at the back.
+        let index = first_max(
+            waiters
+                .iter()
+                .enumerate()
+                .rev()
+                .map(|(i, t)| (i, t.effective_priority())),
+        )?;
+        waiters.remove(index)
+    }
+
     /// Get the current value of a semaphore
     pub fn value(&self) -> usize {
         self.value.get()
@@ -1,14 +1,25 @@
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use core::cell::RefCell;
+use core::sync::atomic::Ordering::SeqCst;
 
-use crate::sync::{Lock, Semaphore};
+use crate::sbi;
+use crate::sync::{Lock, Semaphore, Spin};
 use crate::thread::{self, Thread};
+use crate::trace::{self, Event};
 
 /// Sleep lock. Uses [`Semaphore`] under the hood.
+///
+/// A thread blocked on the lock donates its effective priority to the holder,
+/// see [`Thread::refresh_priority`] for how donations are propagated.
 #[derive(Clone)]
 pub struct Sleep {
     inner: Semaphore,
     holder: RefCell<Option<Arc<Thread>>>,
+    /// Threads blocked in `acquire`, adopted as donors by the next holder.
+    waiters: RefCell<Vec<Arc<Thread>>>,
+    /// Guards `holder` and `waiters` against other harts
+    guard: Spin,
 }
 
 impl Default for Sleep {
@@ -16,24 +27,66 @@ impl Default for Sleep {
         Self {
             inner: Semaphore::new(1),
             holder: Default::default(),
+            waiters: Default::default(),
+            guard: Spin::new(),
         }
     }
 }
 
+impl Sleep {
+    /// Address of the lock, used to tag donations made through it.
+    fn id(&self) -> usize {
+        self as *const _ as usize
+    }
+}
+
 impl Lock for Sleep {
+    const SLEEPS: bool = true;
+
     fn acquire(&self) {
+        let old = sbi::interrupt::set(false);
+        let current = thread::current();
+        current.locks_held.fetch_add(1, SeqCst);
+
+        self.guard.acquire();
+        if let Some(holder) = self.holder.borrow().as_ref() {
+            holder.add_donor(self.id(), current.clone());
+        }
+        self.waiters.borrow_mut().push(current.clone());
+        self.guard.release();
+
         self.inner.down();
-        self.holder.borrow_mut().replace(thread::current());
+
+        // Threads still waiting now donate to us instead.
+        self.guard.acquire();
+        self.waiters
+            .borrow_mut()
+            .retain(|t| !Arc::ptr_eq(t, &current));
+        current.stop_waiting();
+        for waiter in self.waiters.borrow().iter() {
+            current.add_donor(self.id(), waiter.clone());
+        }
+
+        trace::record_of(current.id(), Event::Acquire(self.id()));
+        self.holder.borrow_mut().replace(current);
+        self.guard.release();
+        sbi::interrupt::set(old);
     }
 
     fn release(&self) {
-        assert!(Arc::ptr_eq(
-            self.holder.borrow().as_ref().unwrap(),
-            &thread::current()
-        ));
+        let old = sbi::interrupt::set(false);
+        let current = thread::current();
 
+        self.guard.acquire();
+        assert!(Arc::ptr_eq(self.holder.borrow().as_ref().unwrap(), &current));
         self.holder.borrow_mut().take().unwrap();
+        current.remove_donors(self.id());
+        self.guard.release();
+        trace::record_of(current.id(), Event::Release(self.id()));
+        current.locks_held.fetch_sub(1, SeqCst);
+
         self.inner.up();
+        sbi::interrupt::set(old);
     }
 }
 
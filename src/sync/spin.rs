@@ -1,9 +1,15 @@
+use core::hint;
 use core::sync::atomic::{AtomicBool, Ordering::SeqCst};
 
-use crate::sbi::interrupt;
+use crate::smp;
 use crate::sync::Lock;
 
 /// Spin lock.
+///
+/// Interrupts are turned off on the local hart while the lock is held, so that
+/// interrupt handlers can take it too, and restored once the hart holds no spin
+/// lock anymore (see [`smp::push_off`]). Waiting for the lock only makes sense
+/// when another hart holds it.
 #[derive(Debug, Default)]
 pub struct Spin(AtomicBool);
 
@@ -11,16 +17,37 @@ impl Spin {
     pub const fn new() -> Self {
         Self(AtomicBool::new(false))
     }
+
+    /// Acquires the lock if it's free, without waiting. Returns whether it
+    /// did.
+    pub fn try_acquire(&self) -> bool {
+        smp::push_off();
+        if self.0.swap(true, SeqCst) {
+            smp::pop_off();
+            return false;
+        }
+        true
+    }
+}
+
+impl Clone for Spin {
+    /// A clone is always unlocked.
+    fn clone(&self) -> Self {
+        Self::new()
+    }
 }
 
 impl Lock for Spin {
     fn acquire(&self) {
-        while self.0.fetch_or(true, SeqCst) {
-            assert!(interrupt::get(), "may block");
+        smp::push_off();
+        while self.0.swap(true, SeqCst) {
+            assert!(smp::online() > 1, "may block");
+            hint::spin_loop();
         }
     }
 
     fn release(&self) {
         self.0.store(false, SeqCst);
+        smp::pop_off();
     }
 }
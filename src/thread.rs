@@ -1,27 +1,38 @@
 //! Kernel Threads
 
 pub mod imp;
+pub mod join;
 pub mod manager;
 pub mod scheduler;
+pub mod stack;
 pub mod switch;
+pub mod usage;
 
 pub use self::imp::*;
+pub use self::join::JoinHandle;
 pub use self::manager::Manager;
+pub use self::stack::Stack;
+pub use self::usage::Usage;
 pub(self) use self::scheduler::{Schedule, Scheduler};
 
 use alloc::sync::Arc;
+use core::sync::atomic::Ordering::SeqCst;
+use riscv::register::sstatus;
 
-/// Create a new thread
-pub fn spawn<F>(name: &'static str, f: F) -> Arc<Thread>
+use crate::trace::{self, Event};
+
+/// Create a new thread, whose return value can be taken by [`JoinHandle::join`]
+pub fn spawn<F, T>(name: &'static str, f: F) -> JoinHandle<T>
 where
-    F: FnOnce() + Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
 {
     Builder::new(f).name(name).spawn()
 }
 
 /// Get the current running thread
 pub fn current() -> Arc<Thread> {
-    Manager::get().current.lock().clone()
+    Manager::get().current()
 }
 
 /// Yield the control to another thread (if there's another one ready to run).
@@ -31,13 +42,17 @@ pub fn schedule() {
 
 /// Gracefully shut down the current thread, and schedule another one.
 pub fn exit() -> ! {
+    // Don't get switched out while holding a reference to ourselves.
+    crate::sbi::interrupt::set(false);
+
     {
-        let current = Manager::get().current.lock();
+        let current = current();
 
         #[cfg(feature = "debug")]
-        kprintln!("Exit: {:?}", *current);
+        kprintln!("Exit: {:?}", current);
 
         current.set_status(Status::Dying);
+        current.exited.up();
     }
 
     schedule();
@@ -65,25 +80,119 @@ pub fn wake_up(thread: Arc<Thread>) {
 
     #[cfg(feature = "debug")]
     kprintln!("[THREAD] Wake up {:?}", thread);
+    trace::record(Event::Wakeup(thread.id()));
 
     Manager::get().scheduler.lock().register(thread);
 }
 
-/// (Lab1) Sets the current thread's priority to a given value
-pub fn set_priority(_priority: u32) {}
+/// (Lab1) Sets the current thread's priority to a given value. Ignored under
+/// MLFQS, which computes priorities by itself.
+pub fn set_priority(priority: u32) {
+    if cfg!(feature = "thread-scheduler-mlfqs") {
+        return;
+    }
+
+    let current = current();
+    current.priority.store(priority, SeqCst);
+    current.refresh_priority();
+
+    // A ready thread may outrank us now.
+    schedule();
+}
 
 /// (Lab1) Returns the current thread's effective priority.
 pub fn get_priority() -> u32 {
-    0
+    current().effective_priority()
 }
 
-/// (Lab1) Make the current thread sleep for the given ticks.
-pub fn sleep(ticks: i64) {
-    use crate::sbi::timer::{timer_elapsed, timer_ticks};
+/// Sets the current thread's nice value, clamped to [`NICE_MIN`]..=[`NICE_MAX`],
+/// and yields if it no longer has the highest priority.
+pub fn set_nice(nice: i32) {
+    let current = current();
+    current.nice.store(nice.clamp(NICE_MIN, NICE_MAX), SeqCst);
+
+    #[cfg(feature = "thread-scheduler-mlfqs")]
+    scheduler::mlfqs::update_priority(&current);
+
+    schedule();
+}
+
+/// Returns the current thread's nice value.
+pub fn get_nice() -> i32 {
+    current().nice.load(SeqCst)
+}
 
-    let start = timer_ticks();
+/// Returns 100 times the current thread's `recent_cpu`, rounded.
+pub fn get_recent_cpu() -> i32 {
+    let recent_cpu = scheduler::mlfqs::Fixed::from_bits(current().recent_cpu.load(SeqCst));
+    (recent_cpu * 100).round()
+}
 
-    while timer_elapsed(start) < ticks {
+/// Returns 100 times the system load average, rounded.
+pub fn get_load_avg() -> i32 {
+    (scheduler::mlfqs::load_avg() * 100).round()
+}
+
+/// Yield the CPU if `thread` outranks the current thread.
+///
+/// Nothing happens inside interrupt handlers (`sstatus.SIE` is cleared there),
+/// since they decide on their own whether to schedule before returning.
+pub(crate) fn preempt(thread: &Thread) {
+    if sstatus::read().sie() && scheduler::edf::outranks(thread, &current()) {
+        schedule();
+    }
+}
+
+/// Body of the idle thread of each hart, which runs whatever becomes ready,
+/// and waits for interrupts with `wfi` in between.
+///
+/// On a single hart, nothing can become ready until an interrupt comes, so
+/// the timer is only set for the earliest sleeper, or the next event of the
+/// scheduler, instead of every tick.
+pub(crate) fn idle() -> ! {
+    use crate::sbi::{interrupt, timer};
+    use crate::smp;
+
+    loop {
+        // Nothing may become ready between finding nothing and `wfi`.
+        interrupt::set(false);
         schedule();
+
+        let tickless = smp::online() == 1;
+        if tickless {
+            timer::oneshot(Manager::get().next_wakeup());
+        }
+
+        // `wfi` returns on a pending interrupt, which is taken once `SIE` is set.
+        unsafe {
+            sstatus::clear_sie();
+            interrupt::set(true);
+            riscv::asm::wfi();
+            sstatus::set_sie();
+        }
+
+        // Catch up on the ticks skipped, if any, and get ready to preempt
+        // whoever runs next.
+        if tickless {
+            timer::next();
+        }
+    }
+}
+
+/// (Lab1) Make the current thread sleep for the given ticks. The thread stays
+/// [`Blocked`](Status::Blocked) until the timer interrupt wakes it up.
+pub fn sleep(ticks: i64) {
+    use crate::sbi::{interrupt, timer::timer_ticks};
+
+    if ticks <= 0 {
+        return;
     }
+
+    // The wakeup tick must not pass before we are blocked.
+    let old = interrupt::set(false);
+
+    Manager::get().add_sleeper(current(), timer_ticks() + ticks);
+    schedule();
+
+    interrupt::set(old);
 }
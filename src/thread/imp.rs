@@ -4,24 +4,37 @@ use alloc::boxed::Box;
 use alloc::sync::Arc;
 use core::arch::global_asm;
 use core::fmt::{self, Debug};
-use core::sync::atomic::{AtomicIsize, AtomicU32, Ordering::SeqCst};
+use core::sync::atomic::{
+    AtomicBool, AtomicI32, AtomicIsize, AtomicU32, AtomicU64, AtomicUsize, Ordering::SeqCst,
+};
 
 use crate::mem::mappingtable::MappingTable;
-use crate::mem::{kalloc, kfree, PageTable, PG_SIZE};
+use crate::mem::{PageTable, PG_SIZE};
 use crate::sbi::interrupt;
 use crate::sync::Semaphore;
-use crate::thread::Manager;
+use crate::thread::join::{JoinHandle, Packet};
+use crate::thread::scheduler::edf::Reservation;
+use crate::thread::stack::Stack;
+use crate::thread::{Manager, Usage};
+use crate::trace::{self, Event};
 use crate::userproc::UserProc;
+use crate::Result;
 
 pub const PRI_DEFAULT: u32 = 31;
 pub const PRI_MAX: u32 = 63;
 pub const PRI_MIN: u32 = 0;
+pub const NICE_DEFAULT: i32 = 0;
+pub const NICE_MAX: i32 = 20;
+pub const NICE_MIN: i32 = -20;
+/// Default size of kernel stacks, see [`Builder::stack_size`]
 pub const STACK_SIZE: usize = PG_SIZE * 4;
 pub const STACK_ALIGN: usize = 16;
 pub const STACK_TOP: usize = 0x80500000;
-pub const MAGIC: usize = 0xdeadbeef;
 
-pub type Mutex<T> = crate::sync::Mutex<T, crate::sync::Intr>;
+/// Lock of states only the thread itself uses, which it may hold while blocked.
+pub type Mutex<T> = crate::sync::Mutex<T, crate::sync::Primitive>;
+/// Lock of states that other harts may touch while the thread runs.
+type SpinMutex<T> = crate::sync::Mutex<T, crate::sync::Spin>;
 
 use crate::childinfo::ChildInfo;
 use alloc::vec::Vec;
@@ -33,25 +46,61 @@ use crate::fdlist::FDList;
 pub struct Thread {
     tid: isize,
     name: &'static str,
-    stack: usize,
-    status: Mutex<Status>,
-    context: Mutex<Context>,
+    stack: Stack,
+    status: SpinMutex<Status>,
+    context: SpinMutex<Context>,
+    /// Whether a hart is running on this thread's stack, which is true from
+    /// being picked by [`Manager::schedule`] until its context is saved.
+    pub(super) on_cpu: AtomicBool,
     pub priority: AtomicU32,
+    /// Base priority raised by donations from threads waiting on our locks.
+    effective_priority: AtomicU32,
+    /// Threads blocked on a lock this thread holds, tagged with that lock's address.
+    donors: SpinMutex<Vec<(usize, Arc<Thread>)>>,
+    /// Holder of the lock this thread is blocked on.
+    waiting_on: SpinMutex<Option<Arc<Thread>>>,
+    /// Niceness towards other threads, used by the MLFQS scheduler.
+    pub nice: AtomicI32,
+    /// Recently used CPU time, as the bits of a [`Fixed`](crate::thread::scheduler::mlfqs::Fixed).
+    pub recent_cpu: AtomicI32,
+    /// Virtual time consumed under the stride scheduler.
+    pub pass: AtomicU64,
+    /// Resources used by this thread
+    pub usage: SpinMutex<Usage>,
+    /// Resources used by children that were waited for, and by the children
+    /// they waited for in turn. A child is added when it's reaped, not when it
+    /// exits.
+    pub children_usage: SpinMutex<Usage>,
     pub userproc: Option<UserProc>,
-    pub pagetable: Option<Mutex<PageTable>>,
+    pub pagetable: Option<SpinMutex<PageTable>>,
 
-    pub children: Mutex<Vec<ChildInfo>>,
-    pub parent: Mutex<Option<Arc<Thread>>>,
+    pub children: SpinMutex<Vec<ChildInfo>>,
+    pub parent: SpinMutex<Option<Arc<Thread>>>,
     pub fdlist: Mutex<FDList>,
 
-    pub mapping_table: Mutex<MappingTable>,
-    pub supplementary_pagetable: Mutex<MappingTable>,
+    pub mapping_table: SpinMutex<MappingTable>,
+    pub supplementary_pagetable: SpinMutex<MappingTable>,
+
+    /// Raised once the thread is [`Dying`](Status::Dying), for its [`JoinHandle`].
+    pub(super) exited: Semaphore,
+
+    /// Reservation of a real-time thread
+    realtime: Option<Reservation>,
+
+    /// Set by [`kill`](crate::userproc::kill), and acted on at the next safe point
+    killed: AtomicBool,
+    /// Whether it's blocked in a [`Semaphore`], from which an `up`, a kill or a
+    /// timeout may wake it up. Only the one that clears it does.
+    pub(crate) interruptible: AtomicBool,
+    /// Sleep locks held or being acquired. A killed thread only dies in a
+    /// [`Semaphore`] if it has none.
+    pub(crate) locks_held: AtomicUsize,
 }
 
 impl Thread {
     pub fn new(
         name: &'static str,
-        stack: usize,
+        stack: Stack,
         priority: u32,
         entry: usize,
         userproc: Option<UserProc>,
@@ -66,19 +115,36 @@ impl Thread {
         Thread {
             tid: TID.fetch_add(1, SeqCst),
             name,
+            context: SpinMutex::new(Context::new(stack.top(), entry)),
             stack,
-            status: Mutex::new(Status::Ready),
-            context: Mutex::new(Context::new(stack, entry)),
+            status: SpinMutex::new(Status::Ready),
+            on_cpu: AtomicBool::new(false),
             priority: AtomicU32::new(priority),
+            effective_priority: AtomicU32::new(priority),
+            donors: SpinMutex::new(Vec::new()),
+            waiting_on: SpinMutex::new(None),
+            nice: AtomicI32::new(NICE_DEFAULT),
+            recent_cpu: AtomicI32::new(0),
+            pass: AtomicU64::new(0),
+            usage: SpinMutex::new(Usage::default()),
+            children_usage: SpinMutex::new(Usage::default()),
             userproc,
-            pagetable: pagetable.map(Mutex::new),
+            pagetable: pagetable.map(SpinMutex::new),
 
-            parent: Mutex::new(parent),
-            children: Mutex::new(Vec::new()),
+            parent: SpinMutex::new(parent),
+            children: SpinMutex::new(Vec::new()),
             fdlist: Mutex::new(FDList::new()),
 
-            mapping_table: Mutex::new(mappingtable.unwrap_or(MappingTable::new())),
-            supplementary_pagetable: Mutex::new(MappingTable::new()),
+            mapping_table: SpinMutex::new(mappingtable.unwrap_or(MappingTable::new())),
+            supplementary_pagetable: SpinMutex::new(MappingTable::new()),
+
+            exited: Semaphore::new(0),
+
+            realtime: None,
+
+            killed: AtomicBool::new(false),
+            interruptible: AtomicBool::new(false),
+            locks_held: AtomicUsize::new(0),
         }
     }
 
@@ -98,12 +164,81 @@ impl Thread {
         *self.status.lock() = status;
     }
 
+    /// Marks the thread for termination, and wakes it up if it's blocked in a
+    /// [`Semaphore`], so that it gets to a safe point soon.
+    pub fn kill(self: &Arc<Self>) {
+        self.killed.store(true, SeqCst);
+        if self.interruptible.swap(false, SeqCst) {
+            super::wake_up(self.clone());
+        }
+    }
+
+    pub fn is_killed(&self) -> bool {
+        self.killed.load(SeqCst)
+    }
+
+    /// Base priority, or the highest priority donated to this thread.
+    pub fn effective_priority(&self) -> u32 {
+        self.effective_priority.load(SeqCst)
+    }
+
+    /// Records that `donor` is blocked on `lock`, which this thread holds.
+    pub fn add_donor(self: &Arc<Self>, lock: usize, donor: Arc<Thread>) {
+        *donor.waiting_on.lock() = Some(self.clone());
+        self.donors.lock().push((lock, donor));
+        self.refresh_priority();
+    }
+
+    /// Forgets all donors blocked on `lock`, typically when `lock` is released.
+    pub fn remove_donors(self: &Arc<Self>, lock: usize) {
+        self.donors.lock().retain(|(l, _)| *l != lock);
+        self.refresh_priority();
+    }
+
+    /// Called once the lock this thread was blocked on has been acquired.
+    pub fn stop_waiting(&self) {
+        *self.waiting_on.lock() = None;
+    }
+
+    /// Recomputes the effective priority and propagates the change along the
+    /// chain of lock holders, which is how nested donation is handled.
+    pub fn refresh_priority(self: &Arc<Self>) {
+        let mut thread = self.clone();
+        loop {
+            let donated = thread
+                .donors
+                .lock()
+                .iter()
+                .map(|(_, donor)| donor.effective_priority())
+                .max()
+                .unwrap_or(PRI_MIN);
+            let effective = donated.max(thread.priority.load(SeqCst));
+
+            // Holders further down the chain only depend on this value.
+            if thread.effective_priority.swap(effective, SeqCst) == effective {
+                break;
+            }
+            trace::record_of(thread.id(), Event::Priority(effective));
+
+            let holder = thread.waiting_on.lock().clone();
+            match holder {
+                Some(holder) => thread = holder,
+                None => break,
+            }
+        }
+    }
+
     pub fn context(&self) -> *mut Context {
         (&*self.context.lock()) as *const _ as *mut _
     }
 
-    pub fn overflow(&self) -> bool {
-        unsafe { (self.stack as *const usize).read() != MAGIC }
+    pub fn stack(&self) -> &Stack {
+        &self.stack
+    }
+
+    /// The reservation if it's a real-time thread
+    pub fn realtime(&self) -> Option<&Reservation> {
+        self.realtime.as_ref()
     }
 
     pub fn userproc(&self) -> Option<&UserProc> {
@@ -131,7 +266,6 @@ impl Drop for Thread {
         #[cfg(feature = "debug")]
         kprintln!("[THREAD] {:?}'s resources are released", self);
 
-        kfree(self.stack as *mut _, STACK_SIZE, STACK_ALIGN);
         if let Some(pt) = &self.pagetable {
             unsafe { pt.lock().destroy() };
         }
@@ -146,33 +280,50 @@ impl Drop for Thread {
 }
 
 /* --------------------------------- BUILDER -------------------------------- */
-pub struct Builder {
+pub struct Builder<T = ()> {
     priority: u32,
     name: &'static str,
+    stack_size: usize,
+    /// Period, runtime and deadline of a real-time thread
+    realtime: Option<(i64, i64, i64)>,
     function: usize,
     userproc: Option<UserProc>,
     pagetable: Option<PageTable>,
 
     parent: Option<Arc<Thread>>,
     mappingtable: Option<MappingTable>,
+    /// Inherited by a forked process, along with `mappingtable`
+    supplementary_pagetable: Option<MappingTable>,
+    fdlist: Option<FDList>,
+    /// Receives the return value of `function`
+    packet: Arc<Packet<T>>,
 }
 
-impl Builder {
+impl<T: Send + 'static> Builder<T> {
     pub fn new<F>(function: F) -> Self
     where
-        F: FnOnce() + Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
     {
+        let packet = Arc::new(Packet::new());
+        let result = packet.clone();
+        let function = move || result.set(function());
+
         // `*mut dyn FnOnce()` is a fat pointer, box it again to ensure FFI-safety.
         let function: *mut Box<dyn FnOnce()> = Box::into_raw(Box::new(Box::new(function)));
 
         Self {
             priority: PRI_DEFAULT,
             name: "Default",
+            stack_size: STACK_SIZE,
+            realtime: None,
             function: function as usize,
             userproc: None,
             pagetable: None,
             parent: None,
             mappingtable: None,
+            supplementary_pagetable: None,
+            fdlist: None,
+            packet,
         }
     }
 
@@ -186,6 +337,20 @@ impl Builder {
         self
     }
 
+    /// Sets the size of the kernel stack, which is rounded up to whole pages.
+    pub fn stack_size(mut self, size: usize) -> Self {
+        self.stack_size = size;
+        self
+    }
+
+    /// Makes it a real-time thread, which runs for `runtime` ticks in every
+    /// `period` ticks, before `deadline` ticks into the period.
+    /// See [`edf`](super::scheduler::edf) for details.
+    pub fn realtime(mut self, period: i64, runtime: i64, deadline: i64) -> Self {
+        self.realtime = Some((period, runtime, deadline));
+        self
+    }
+
     pub fn pagetable(mut self, pagetable: PageTable) -> Self {
         self.pagetable = Some(pagetable);
         self
@@ -206,22 +371,38 @@ impl Builder {
         self
     }
 
-    pub fn build(self) -> Arc<Thread> {
-        let stack = kalloc(STACK_SIZE, STACK_ALIGN) as usize;
+    pub fn set_supplementary_pagetable(mut self, spt: MappingTable) -> Self {
+        self.supplementary_pagetable = Some(spt);
+        self
+    }
 
-        // Put magic number at the bottom of the stack.
-        unsafe { (stack as *mut usize).write(MAGIC) };
+    pub fn fdlist(mut self, fdlist: FDList) -> Self {
+        self.fdlist = Some(fdlist);
+        self
+    }
 
-        Arc::new(Thread::new(
+    pub fn build(self) -> Arc<Thread> {
+        let mut thread = Thread::new(
             self.name,
-            stack,
+            Stack::new(self.stack_size),
             self.priority,
             self.function,
             self.userproc,
             self.pagetable,
             self.parent,
             self.mappingtable,
-        ))
+        );
+        thread.realtime = self
+            .realtime
+            .map(|(period, runtime, deadline)| Reservation::new(period, runtime, deadline));
+        if let Some(spt) = self.supplementary_pagetable {
+            thread.supplementary_pagetable = SpinMutex::new(spt);
+        }
+        if let Some(fdlist) = self.fdlist {
+            thread.fdlist = Mutex::new(fdlist);
+        }
+
+        Arc::new(thread)
     }
 
     /// Spawns a kernel thread and registers it to the [`Manager`].
@@ -229,16 +410,43 @@ impl Builder {
     /// `userproc` and `pagetable` have to be set properly.
     ///
     /// Note that this function CANNOT be called during [`Manager`]'s initialization.
-    pub fn spawn(self) -> Arc<Thread> {
+    ///
+    /// Panics if a real-time thread is not admitted, see [`Builder::try_spawn`].
+    pub fn spawn(self) -> JoinHandle<T> {
+        self.try_spawn().expect("real-time thread not admitted")
+    }
+
+    /// Like [`Builder::spawn`], but fails with [`OsError::Overloaded`](crate::OsError::Overloaded)
+    /// if a real-time thread would overload the CPU.
+    pub fn try_spawn(self) -> Result<JoinHandle<T>> {
+        let packet = self.packet.clone();
+        let function = self.function;
         let new_thread = self.build();
 
+        if new_thread.realtime().is_some() {
+            if let Err(e) = Manager::get().scheduler.lock().admit(&new_thread) {
+                // The thread never runs, so its function is ours to free.
+                drop(unsafe { Box::from_raw(function as *mut Box<dyn FnOnce()>) });
+                return Err(e);
+            }
+        }
+
+        // Niceness and recent CPU usage are inherited from the creator.
+        let creator = super::current();
+        new_thread.nice.store(creator.nice.load(SeqCst), SeqCst);
+        new_thread.recent_cpu.store(creator.recent_cpu.load(SeqCst), SeqCst);
+
+        #[cfg(feature = "thread-scheduler-mlfqs")]
+        super::scheduler::mlfqs::update_priority(&new_thread);
+
         #[cfg(feature = "debug")]
         kprintln!("[THREAD] create {:?}", new_thread);
 
         Manager::get().register(new_thread.clone());
+        super::preempt(&new_thread);
 
         // Off you go
-        new_thread
+        Ok(JoinHandle::new(new_thread, packet))
     }
 }
 
@@ -271,11 +479,10 @@ pub struct Context {
 }
 
 impl Context {
-    fn new(stack: usize, entry: usize) -> Self {
+    fn new(stack_top: usize, entry: usize) -> Self {
         Self {
             ra: kernel_thread_entry as usize,
-            // calculate the address of stack top
-            sp: stack + STACK_SIZE,
+            sp: stack_top,
             // s0 stores a thread's entry point. For a new thread,
             // s0 will then be used as the first argument of `kernel_thread`.
             s: core::array::from_fn(|i| if i == 0 { entry } else { 0 }),
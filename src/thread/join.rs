@@ -0,0 +1,51 @@
+//! Join handles of kernel threads
+
+use alloc::sync::Arc;
+
+use crate::sync::{Mutex, Spin};
+use crate::thread::Thread;
+
+/// Where a thread leaves its return value for the one joining it.
+pub(super) struct Packet<T>(Mutex<Option<T>, Spin>);
+
+impl<T> Packet<T> {
+    pub(super) fn new() -> Self {
+        Self(Mutex::new(None))
+    }
+
+    pub(super) fn set(&self, value: T) {
+        *self.0.lock() = Some(value);
+    }
+}
+
+/// An owned permission to join on a thread, returned by [`Builder::spawn`](super::Builder::spawn).
+///
+/// Dropping the handle detaches the thread, which is then reclaimed as soon as
+/// it exits.
+pub struct JoinHandle<T> {
+    thread: Arc<Thread>,
+    packet: Arc<Packet<T>>,
+}
+
+impl<T> JoinHandle<T> {
+    pub(super) fn new(thread: Arc<Thread>, packet: Arc<Packet<T>>) -> Self {
+        Self { thread, packet }
+    }
+
+    /// The underlying thread
+    pub fn thread(&self) -> &Arc<Thread> {
+        &self.thread
+    }
+
+    /// Blocks until the thread is [`Dying`](super::Status::Dying), then returns
+    /// what its function returned.
+    pub fn join(self) -> T {
+        self.thread.exited.down();
+
+        self.packet
+            .0
+            .lock()
+            .take()
+            .expect("thread exited without a return value")
+    }
+}
@@ -2,35 +2,53 @@
 
 use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::array;
+use core::hint;
 use core::mem;
-use core::ops::DerefMut;
+use core::sync::atomic::Ordering::SeqCst;
 
-use crate::bootstack;
+use crate::{bootstack, bootstack_top};
 use crate::mem::KernelPgTable;
 use crate::sbi::interrupt;
-use crate::sync::Lazy;
+use crate::sbi::timer::timer_ticks;
+use crate::smp::{self, hart_id, MAX_HARTS};
+use crate::sync::{Lazy, Spin};
 use crate::thread::{
-    schedule, switch, Builder, Mutex, Schedule, Scheduler, Status, Thread, MAGIC, PRI_DEFAULT, PRI_MIN
+    switch, wake_up, Builder, Schedule, Scheduler, Stack, Status, Thread, PRI_DEFAULT, PRI_MIN,
+    STACK_SIZE,
 };
+use crate::trace::{self, Event};
+
+/// Lock of the manager's states, which are shared by all harts.
+type Mutex<T> = crate::sync::Mutex<T, Spin>;
 
 /* --------------------------------- MANAGER -------------------------------- */
-/// Global thread manager, contains a scheduler and a current thread.
+/// Global thread manager, contains a scheduler and the current thread of each hart.
 pub struct Manager {
     /// Global thread scheduler
     pub scheduler: Mutex<Scheduler>,
-    /// The current running thread
-    pub current: Mutex<Arc<Thread>>,
+    /// The running thread of each hart, `None` if the hart is offline
+    current: [Mutex<Option<Arc<Thread>>>; MAX_HARTS],
     /// All alive and not yet destroyed threads
     all: Mutex<Vec<Arc<Thread>>>,
+    /// The thread of each hart that runs when no other thread is ready
+    idle: [Mutex<Option<Arc<Thread>>>; MAX_HARTS],
+    /// Sleeping threads and their wakeup ticks, earliest first. The flag marks
+    /// timed waits, which may have been woken up by someone else already.
+    sleepers: Mutex<Vec<(i64, Arc<Thread>, bool)>>,
 }
 
 impl Manager {
     pub fn get() -> &'static Self {
         static TMANAGER: Lazy<Manager> = Lazy::new(|| {
             // Manully create initial thread.
+            let stack = bootstack_top as usize - bootstack as usize;
+            let stack = unsafe { Stack::from_raw(bootstack as usize, stack) };
+            smp::set_stack_limit(stack.bottom());
+
             let initial = Arc::new(Thread::new(
                 "Initial",
-                bootstack as usize,
+                stack,
                 PRI_DEFAULT,
                 0,
                 None,
@@ -38,22 +56,23 @@ impl Manager {
                 None,
                 None,
             ));
-            unsafe { (bootstack as *mut usize).write(MAGIC) };
             initial.set_status(Status::Running);
+            initial.on_cpu.store(true, SeqCst);
 
-            let manager = Manager {
-                scheduler: Mutex::new(Scheduler::default()),
-                all: Mutex::new(Vec::from([initial.clone()])),
-                current: Mutex::new(initial),
-            };
-
-            let idle = Builder::new(|| loop {
-                schedule()
-            })
+            let idle = Builder::new(|| super::idle())
             .name("Idle")
             .priority(PRI_MIN)
             .build();
-            manager.register(idle);
+
+            let manager = Manager {
+                scheduler: Mutex::new(Scheduler::default()),
+                all: Mutex::new(Vec::from([initial.clone(), idle.clone()])),
+                current: array::from_fn(|_| Mutex::new(None)),
+                idle: array::from_fn(|_| Mutex::new(None)),
+                sleepers: Mutex::new(Vec::new()),
+            };
+            *manager.current[hart_id()].lock() = Some(initial);
+            *manager.idle[hart_id()].lock() = Some(idle);
 
             manager
         });
@@ -61,6 +80,46 @@ impl Manager {
         &TMANAGER
     }
 
+    /// Bring the current hart under management. Its boot stack, whose bottom
+    /// is `stack`, becomes the stack of its idle thread, which is running now.
+    pub fn add_hart(&self, stack: usize) {
+        let stack = unsafe { Stack::from_raw(stack, STACK_SIZE) };
+        smp::set_stack_limit(stack.bottom());
+
+        let idle = Arc::new(Thread::new(
+            "Idle",
+            stack,
+            PRI_MIN,
+            0,
+            None,
+            None,
+            None,
+            None,
+        ));
+        idle.set_status(Status::Running);
+        idle.on_cpu.store(true, SeqCst);
+
+        self.all.lock().push(idle.clone());
+        *self.idle[hart_id()].lock() = Some(idle.clone());
+        *self.current[hart_id()].lock() = Some(idle);
+    }
+
+    /// The running thread of the current hart.
+    pub fn current(&self) -> Arc<Thread> {
+        // Don't move to another hart before reading the slot.
+        let old = interrupt::set(false);
+        let current = self.current[hart_id()].lock().clone();
+        interrupt::set(old);
+
+        current.expect("hart is not online")
+    }
+
+    fn is_idle(&self, thread: &Arc<Thread>) -> bool {
+        self.idle
+            .iter()
+            .any(|idle| idle.lock().as_ref().map_or(false, |i| Arc::ptr_eq(i, thread)))
+    }
+
     pub(super) fn register(&self, thread: Arc<Thread>) {
         // Register it into the scheduler
         self.scheduler.lock().register(thread.clone());
@@ -69,10 +128,86 @@ impl Manager {
         self.all.lock().push(thread.clone());
     }
 
-    /// Choose a `ready` thread to run if possible. If found, do as follows:
+    /// Put `thread` to the sleep queue until tick `wakeup`, and mark it as
+    /// [`Blocked`](Status::Blocked). The caller should schedule right after.
+    ///
+    /// Blocking under the queue's lock ensures that a timer interrupt on another
+    /// hart never finds a sleeper that is still running.
+    pub(super) fn add_sleeper(&self, thread: Arc<Thread>, wakeup: i64) {
+        let mut sleepers = self.sleepers.lock();
+        thread.set_status(Status::Blocked);
+
+        Self::insert_sleeper(&mut sleepers, thread, wakeup, false);
+    }
+
+    /// Wake up `thread`, which is blocked in a timed wait, at tick `wakeup`,
+    /// unless something else wakes it up first.
+    pub(crate) fn add_timeout(&self, thread: Arc<Thread>, wakeup: i64) {
+        let mut sleepers = self.sleepers.lock();
+        Self::insert_sleeper(&mut sleepers, thread, wakeup, true);
+    }
+
+    /// Forget the timeout of `thread`, after it has been woken up.
+    pub(crate) fn cancel_timeout(&self, thread: &Arc<Thread>) {
+        self.sleepers
+            .lock()
+            .retain(|(_, t, timeout)| !(*timeout && Arc::ptr_eq(t, thread)));
+    }
+
+    fn insert_sleeper(
+        sleepers: &mut Vec<(i64, Arc<Thread>, bool)>,
+        thread: Arc<Thread>,
+        wakeup: i64,
+        timeout: bool,
+    ) {
+        // Threads waking up at the same tick keep their arrival order.
+        let index = sleepers.partition_point(|(t, _, _)| *t <= wakeup);
+        sleepers.insert(index, (wakeup, thread, timeout));
+    }
+
+    /// The tick at which the earliest sleeper wakes up, or the scheduler has
+    /// work to do, whichever comes first
+    pub(super) fn next_wakeup(&self) -> Option<i64> {
+        let sleeper = self.sleepers.lock().first().map(|(wakeup, _, _)| *wakeup);
+        sleeper.into_iter().chain(self.scheduler.lock().next_event()).min()
+    }
+
+    /// Wake up the sleepers whose time has come, then forward the tick to the
+    /// scheduler. Called from the timer interrupt of the boot hart.
+    pub fn tick(&self) {
+        let now = timer_ticks();
+        let woken: Vec<_> = {
+            let mut sleepers = self.sleepers.lock();
+            let due = sleepers.partition_point(|(t, _, _)| *t <= now);
+            sleepers.drain(..due).collect()
+        };
+        for (_, thread, timeout) in woken {
+            // A timed wait is ended by whoever clears `interruptible` first.
+            if !timeout || thread.interruptible.swap(false, SeqCst) {
+                wake_up(thread);
+            }
+        }
+
+        let current = self.current();
+        let current = (!self.is_idle(&current)).then_some(&current);
+
+        let all: Vec<_> = self
+            .all
+            .lock()
+            .iter()
+            .filter(|t| !self.is_idle(t))
+            .cloned()
+            .collect();
+
+        self.scheduler.lock().tick(current, &all);
+    }
+
+    /// Choose a `ready` thread to run if possible, or the idle thread if the
+    /// current one can't keep running. If found, do as follows:
     ///
-    /// 1. Turn off intr. Mark the `next` thread as [`Running`](Status::Running) and
-    /// change manager's current thread.
+    /// 1. Turn off intr. Wait until the `next` thread has been switched out on
+    /// other harts. Mark it as [`Running`](Status::Running) and change the
+    /// hart's current thread.
     ///
     /// 2. Forward the `previous` thread to [`schedule_tail`] through [`switch`].
     /// In [`schedule_tail`], the finishing touches of the schedule is done in the
@@ -81,32 +216,56 @@ impl Manager {
     /// 3. Get back from the other thread and restore the intr setting.
     pub fn schedule(&self) {
         let old = interrupt::set(false);
+        let hart = hart_id();
+
+        let current = self.current();
+        if current.status() == Status::Blocked {
+            trace::record_of(current.id(), Event::Block);
+        }
 
-        let next = self.scheduler.lock().schedule();
+        let next = self.scheduler.lock().schedule().or_else(|| {
+            // Nothing else is ready.
+            (current.status() != Status::Running).then(|| self.idle[hart].lock().clone().unwrap())
+        });
 
-        // Make sure there's at least one thread runnable.
-        assert!(
-            self.current.lock().status() == Status::Running || next.is_some(),
-            "no thread is ready"
-        );
-        assert!(
-            !self.current.lock().overflow(),
-            "Current thread has overflowed its stack."
-        );
+        if let Some(next) = next.as_ref().filter(|next| Arc::ptr_eq(next, &current)) {
+            // The current thread blocked but was woken up by another hart
+            // before switching out, so just keep running it.
+            next.set_status(Status::Running);
+        } else if let Some(next) = next {
+            // A thread that blocked on another hart may be woken up before it
+            // is switched out there. Its context is only saved after that.
+            while next.on_cpu.load(SeqCst) {
+                hint::spin_loop();
+            }
 
-        if let Some(next) = next {
             assert_eq!(next.status(), Status::Ready);
-            assert!(!next.overflow(), "Next thread has overflowed its stack.");
+            next.on_cpu.store(true, SeqCst);
             next.set_status(Status::Running);
 
+            {
+                let mut usage = current.usage.lock();
+                match current.status() {
+                    Status::Running => usage.involuntary_switches += 1,
+                    _ => usage.voluntary_switches += 1,
+                }
+            }
+
+            trace::record_of(current.id(), Event::Switch(next.id()));
+
             // Update the current thread to the next running thread
-            let previous = mem::replace(self.current.lock().deref_mut(), next);
+            let new_ctx = next.context();
+            let next_stack = next.stack().bottom();
+            let previous = mem::replace(&mut *self.current[hart].lock(), Some(next)).unwrap();
+            drop(current);
             #[cfg(feature = "debug")]
             kprintln!("[THREAD] switch from {:?}", previous);
 
             // Retrieve the raw pointers of two threads' context
             let old_ctx = previous.context();
-            let new_ctx = self.current.lock().context();
+
+            // No trap may happen until we are on the new stack.
+            smp::set_stack_limit(next_stack);
 
             // WARNING: This function call may not return, so don't expect any value to be dropped.
 
@@ -127,29 +286,38 @@ impl Manager {
 
     /// After context switch, now do some finishing touches. We release a thread's
     /// resources if it's about to be destroyed. For a runnable thread, it should
-    /// be registered into the scheduler.
+    /// be registered into the scheduler, unless it is an idle thread.
     ///
     /// Note: This function is running on the stack of the new thread.
     pub fn schedule_tail(&self, previous: Arc<Thread>) {
         assert!(!interrupt::get());
 
         #[cfg(feature = "debug")]
-        kprintln!("[THREAD] switch to {:?}", *self.current.lock());
+        kprintln!("[THREAD] switch to {:?}", self.current());
 
         match previous.status() {
             Status::Dying => {
                 // A thread's resources should be released at this point
                 self.all.lock().retain(|t| t.id() != previous.id());
+                self.scheduler.lock().retire(&previous);
+                #[cfg(feature = "lockdep")]
+                crate::sync::lockdep::forget(previous.id());
             }
             Status::Running => {
                 previous.set_status(Status::Ready);
-                self.scheduler.lock().register(previous);
+                if !self.is_idle(&previous) {
+                    self.scheduler.lock().register(previous.clone());
+                }
             }
-            Status::Blocked => {}
-            Status::Ready => unreachable!(),
+            // Woken up by another hart before switching out, so it's already
+            // in the scheduler.
+            Status::Blocked | Status::Ready => {}
         }
 
-        if let Some(pt) = self.current.lock().pagetable.as_ref() {
+        // Its context is saved, other harts may run it now.
+        previous.on_cpu.store(false, SeqCst);
+
+        if let Some(pt) = self.current().pagetable.as_ref() {
             pt.lock().activate();
         } else {
             KernelPgTable::get().activate();
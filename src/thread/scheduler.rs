@@ -4,18 +4,44 @@
 //! FCFS is an example implementation of a scheduler, you can add new schedulers by implementing
 //! [`Schedule`] trait.
 //!
+//! The policy chosen by features only schedules normal threads. Real-time threads are scheduled
+//! before them by the [`Edf`](edf::Edf) class.
+//!
 
+pub mod edf;
 pub mod fcfs;
+pub mod mlfqs;
+pub mod priority;
+pub mod stride;
 
 use alloc::sync::Arc;
 
 use crate::thread::Thread;
 
-#[cfg(feature = "thread-scheduler-priority")]
-// (Lab1) Your task: priority scheduling
-pub type Scheduler = self::fcfs::Fcfs;
-#[cfg(not(feature = "thread-scheduler-priority"))]
-pub type Scheduler = self::fcfs::Fcfs;
+#[cfg(feature = "thread-scheduler-mlfqs")]
+pub type Policy = self::mlfqs::Mlfqs;
+#[cfg(all(
+    feature = "thread-scheduler-stride",
+    not(feature = "thread-scheduler-mlfqs")
+))]
+pub type Policy = self::stride::Stride;
+#[cfg(all(
+    feature = "thread-scheduler-priority",
+    not(any(
+        feature = "thread-scheduler-mlfqs",
+        feature = "thread-scheduler-stride"
+    ))
+))]
+pub type Policy = self::priority::Priority;
+#[cfg(not(any(
+    feature = "thread-scheduler-priority",
+    feature = "thread-scheduler-mlfqs",
+    feature = "thread-scheduler-stride"
+)))]
+pub type Policy = self::fcfs::Fcfs;
+
+/// Real-time threads first, then the others by the chosen policy
+pub type Scheduler = self::edf::Edf<Policy>;
 
 /// Basic functionalities of thread schedulers
 pub trait Schedule: Default {
@@ -26,4 +52,28 @@ pub trait Schedule: Default {
     /// Choose the next thread to run. `None` if scheduler decides to keep running
     /// the current thread.
     fn schedule(&mut self) -> Option<Arc<Thread>>;
+
+    /// Notify the scheduler that a timer tick has passed. `current` is `None`
+    /// if the idle thread is running, and `all` holds every live thread except
+    /// the idle one.
+    fn tick(&mut self, _current: Option<&Arc<Thread>>, _all: &[Arc<Thread>]) {}
+
+    /// The next tick whose [`tick`](Schedule::tick) has work to do even if no
+    /// thread runs, so that an idle hart skipping ticks wakes up for it.
+    /// `None` if there is none.
+    fn next_event(&self) -> Option<i64> {
+        None
+    }
+}
+
+/// Index of the first of `items` with the greatest key, each given with its
+/// index. Threads are listed oldest first, so that among equals, the one that
+/// has waited the longest is chosen.
+pub fn first_max<K: Ord>(items: impl Iterator<Item = (usize, K)>) -> Option<usize> {
+    items
+        .fold(None, |max: Option<(usize, K)>, (i, key)| match max {
+            Some((_, ref m)) if *m >= key => max,
+            _ => Some((i, key)),
+        })
+        .map(|(i, _)| i)
 }
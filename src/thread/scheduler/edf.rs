@@ -0,0 +1,223 @@
+//! Earliest-deadline-first real-time class.
+//!
+//! A real-time thread reserves `runtime` ticks of CPU time in every `period`
+//! ticks, which must be used up within `deadline` ticks from the start of the
+//! period. Ready real-time threads always run before the threads of the normal
+//! policy, and among themselves, the one of the earliest deadline runs first.
+//!
+//! A thread is only admitted if the total utilization (`runtime / period`) of
+//! real-time threads stays within 100% of the CPU. A thread that has used up
+//! its budget is throttled, i.e. kept off the CPU until its next period.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicI64, Ordering::SeqCst};
+
+use crate::sbi::timer::timer_ticks;
+use crate::thread::{self, Schedule, Status, Thread};
+use crate::{OsError, Result};
+
+/// Utilization of the whole CPU.
+const FULL: u64 = 1 << 20;
+
+/// The reservation of a real-time thread. All times are in timer ticks.
+#[derive(Debug)]
+pub struct Reservation {
+    period: i64,
+    runtime: i64,
+    deadline: i64,
+    /// Start of the next period
+    release: AtomicI64,
+    /// Deadline of the current period
+    abs_deadline: AtomicI64,
+    /// Ticks left in the budget of the current period
+    budget: AtomicI64,
+    /// Whether the thread is kept off the CPU until its next period
+    throttled: AtomicBool,
+}
+
+impl Reservation {
+    pub fn new(period: i64, runtime: i64, deadline: i64) -> Self {
+        assert!(
+            0 < runtime && runtime <= deadline && deadline <= period,
+            "invalid real-time reservation"
+        );
+
+        Self {
+            period,
+            runtime,
+            deadline,
+            release: AtomicI64::new(0),
+            abs_deadline: AtomicI64::new(0),
+            budget: AtomicI64::new(0),
+            throttled: AtomicBool::new(false),
+        }
+    }
+
+    /// Absolute deadline of the current period.
+    pub fn deadline(&self) -> i64 {
+        self.abs_deadline.load(SeqCst)
+    }
+
+    /// Ticks left in the budget of the current period.
+    pub fn budget(&self) -> i64 {
+        self.budget.load(SeqCst)
+    }
+
+    pub fn is_throttled(&self) -> bool {
+        self.throttled.load(SeqCst)
+    }
+
+    /// Share of the CPU, rounded up so that admission errs on the safe side.
+    fn utilization(&self) -> u64 {
+        (self.runtime as u64 * FULL).div_ceil(self.period as u64)
+    }
+
+    /// Starts a new period at `now`, or at the release time if not late.
+    fn replenish(&self, now: i64) {
+        let start = self.release.load(SeqCst).max(now);
+        self.abs_deadline.store(start + self.deadline, SeqCst);
+        self.budget.store(self.runtime, SeqCst);
+        self.release.store(start + self.period, SeqCst);
+    }
+}
+
+/// Whether `thread` should run before `other`: real-time threads come first,
+/// ordered by their deadlines, and the others by their priorities.
+pub fn outranks(thread: &Thread, other: &Thread) -> bool {
+    match (thread.realtime(), other.realtime()) {
+        (Some(rt), Some(other)) => rt.deadline() < other.deadline(),
+        (Some(_), None) => true,
+        (None, Some(_)) => false,
+        (None, None) => thread.effective_priority() > other.effective_priority(),
+    }
+}
+
+/// The real-time class on top of the normal scheduling policy `S`.
+#[derive(Default)]
+pub struct Edf<S> {
+    /// Ready real-time threads
+    ready: Vec<Arc<Thread>>,
+    /// Admitted real-time threads that are alive
+    admitted: Vec<Arc<Thread>>,
+    /// Total utilization of admitted threads
+    utilization: u64,
+    /// Policy of the other threads
+    normal: S,
+}
+
+impl<S> Edf<S> {
+    /// Admits a real-time thread before it is registered, or fails if the CPU
+    /// would be overloaded.
+    pub fn admit(&mut self, thread: &Arc<Thread>) -> Result<()> {
+        let rt = thread.realtime().expect("not a real-time thread");
+
+        if self.utilization + rt.utilization() > FULL {
+            return Err(OsError::Overloaded);
+        }
+        self.utilization += rt.utilization();
+
+        rt.replenish(timer_ticks());
+        self.admitted.push(thread.clone());
+        Ok(())
+    }
+
+    /// Gives back the reservation of a dying thread.
+    pub fn retire(&mut self, thread: &Thread) {
+        if let Some(rt) = thread.realtime() {
+            let count = self.admitted.len();
+            self.admitted.retain(|t| t.id() != thread.id());
+            if self.admitted.len() < count {
+                self.utilization -= rt.utilization();
+            }
+        }
+    }
+
+    /// Index of the ready thread of the earliest deadline, the first one on ties.
+    fn earliest(&self) -> Option<usize> {
+        let mut earliest: Option<(usize, i64)> = None;
+        for (i, t) in self.ready.iter().enumerate() {
+            let deadline = t.realtime().unwrap().deadline();
+            if earliest.map_or(true, |(_, d)| deadline < d) {
+                earliest = Some((i, deadline));
+            }
+        }
+        earliest.map(|(i, _)| i)
+    }
+}
+
+impl<S: Schedule> Schedule for Edf<S> {
+    fn register(&mut self, thread: Arc<Thread>) {
+        match thread.realtime() {
+            Some(_) => self.ready.push(thread),
+            None => self.normal.register(thread),
+        }
+    }
+
+    fn schedule(&mut self) -> Option<Arc<Thread>> {
+        let current = thread::current();
+        let realtime = current.status() == Status::Running && current.realtime().is_some();
+
+        if let Some(index) = self.earliest() {
+            if realtime && !outranks(&self.ready[index], &current) {
+                return None;
+            }
+            return Some(self.ready.remove(index));
+        }
+
+        // A running real-time thread beats any normal one.
+        if realtime {
+            return None;
+        }
+        self.normal.schedule()
+    }
+
+    fn tick(&mut self, current: Option<&Arc<Thread>>, all: &[Arc<Thread>]) {
+        let now = timer_ticks();
+
+        // Charge the running thread, and throttle it if it overran its budget.
+        // Blocking it makes the manager switch to someone else right away.
+        if let Some(current) = current.filter(|t| t.status() == Status::Running) {
+            if let Some(rt) = current.realtime() {
+                if rt.budget.fetch_sub(1, SeqCst) <= 1 {
+                    rt.throttled.store(true, SeqCst);
+                    current.set_status(Status::Blocked);
+                }
+            }
+        }
+
+        // Start new periods, letting throttled threads run again.
+        for t in self.admitted.iter() {
+            let rt = t.realtime().unwrap();
+            if now < rt.release.load(SeqCst) {
+                continue;
+            }
+
+            rt.replenish(now);
+            if rt.throttled.swap(false, SeqCst) {
+                t.set_status(Status::Ready);
+                self.ready.push(t.clone());
+            }
+        }
+
+        let current = current.filter(|t| t.realtime().is_none());
+        let all: Vec<_> = all
+            .iter()
+            .filter(|t| t.realtime().is_none())
+            .cloned()
+            .collect();
+        self.normal.tick(current, &all);
+    }
+
+    /// Throttled threads are released at the start of their next periods.
+    fn next_event(&self) -> Option<i64> {
+        let release = self
+            .admitted
+            .iter()
+            .map(|t| t.realtime().unwrap())
+            .filter(|rt| rt.is_throttled())
+            .map(|rt| rt.release.load(SeqCst))
+            .min();
+        release.into_iter().chain(self.normal.next_event()).min()
+    }
+}
@@ -0,0 +1,128 @@
+//! 4.4BSD multilevel feedback queue scheduler.
+//!
+//! Priorities are not set by threads but computed from their `nice` value and
+//! how much CPU time they have used recently:
+//!
+//! - `priority = PRI_MAX - recent_cpu / 4 - nice * 2`, every 4 ticks;
+//! - `recent_cpu += 1` for the running thread, every tick;
+//! - `recent_cpu = (2 * load_avg) / (2 * load_avg + 1) * recent_cpu + nice`
+//!   and `load_avg = (59 / 60) * load_avg + (1 / 60) * ready_threads`, every second.
+//!
+//! Ready threads wait in one of 64 round-robin queues, one per priority.
+
+mod fixed;
+
+pub use self::fixed::Fixed;
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicI32, Ordering::SeqCst};
+
+use crate::sbi::timer::{timer_ticks, TICKS_PER_SEC};
+use crate::thread::{self, Schedule, Status, Thread, PRI_MAX, PRI_MIN};
+
+const QUEUE_CNT: usize = (PRI_MAX - PRI_MIN + 1) as usize;
+
+/// Priorities are recomputed every `PRIORITY_PERIOD` ticks.
+const PRIORITY_PERIOD: i64 = 4;
+
+/// Bits of the system load average.
+static LOAD_AVG: AtomicI32 = AtomicI32::new(0);
+
+/// Estimated number of threads ready to run over the past minute.
+pub fn load_avg() -> Fixed {
+    Fixed::from_bits(LOAD_AVG.load(SeqCst))
+}
+
+/// Recomputes `thread`'s priority from its `nice` and `recent_cpu`.
+pub fn update_priority(thread: &Arc<Thread>) {
+    let recent_cpu = Fixed::from_bits(thread.recent_cpu.load(SeqCst));
+    let nice = thread.nice.load(SeqCst);
+
+    let priority = (Fixed::from_int(PRI_MAX as i32) - recent_cpu / 4 - nice * 2).trunc();
+    let priority = priority.clamp(PRI_MIN as i32, PRI_MAX as i32) as u32;
+
+    thread.priority.store(priority, SeqCst);
+    thread.refresh_priority();
+}
+
+fn update_recent_cpu(thread: &Thread, load_avg: Fixed) {
+    let recent_cpu = Fixed::from_bits(thread.recent_cpu.load(SeqCst));
+    let nice = thread.nice.load(SeqCst);
+
+    let decay = load_avg * 2 / (load_avg * 2 + 1);
+    thread
+        .recent_cpu
+        .store((decay * recent_cpu + nice).bits(), SeqCst);
+}
+
+pub struct Mlfqs {
+    queues: [VecDeque<Arc<Thread>>; QUEUE_CNT],
+}
+
+impl Default for Mlfqs {
+    fn default() -> Self {
+        Self {
+            queues: core::array::from_fn(|_| VecDeque::new()),
+        }
+    }
+}
+
+impl Mlfqs {
+    /// The highest priority with a non-empty queue.
+    fn highest(&self) -> Option<usize> {
+        (0..QUEUE_CNT).rev().find(|&p| !self.queues[p].is_empty())
+    }
+}
+
+impl Schedule for Mlfqs {
+    fn register(&mut self, thread: Arc<Thread>) {
+        let priority = thread.effective_priority() as usize;
+        self.queues[priority].push_back(thread)
+    }
+
+    fn schedule(&mut self) -> Option<Arc<Thread>> {
+        let priority = self.highest()?;
+
+        // Keep running the current thread if no one outranks it.
+        let current = thread::current();
+        if current.status() == Status::Running && current.effective_priority() as usize > priority
+        {
+            return None;
+        }
+
+        self.queues[priority].pop_front()
+    }
+
+    fn tick(&mut self, current: Option<&Arc<Thread>>, all: &[Arc<Thread>]) {
+        let ticks = timer_ticks();
+
+        if let Some(current) = current {
+            current.recent_cpu.fetch_add(Fixed::from_int(1).bits(), SeqCst);
+        }
+
+        if ticks % TICKS_PER_SEC as i64 == 0 {
+            let ready = all.iter().filter(|t| t.status() == Status::Ready).count()
+                + current.is_some() as usize;
+            let load_avg = load_avg() * 59 / 60 + Fixed::from_int(ready as i32) / 60;
+            LOAD_AVG.store(load_avg.bits(), SeqCst);
+
+            all.iter().for_each(|t| update_recent_cpu(t, load_avg));
+        }
+
+        if ticks % PRIORITY_PERIOD == 0 {
+            all.iter().for_each(update_priority);
+
+            // Move ready threads to the queues of their new priorities.
+            let ready: Vec<_> = self.queues.iter_mut().flat_map(|q| q.drain(..)).collect();
+            ready.into_iter().for_each(|t| self.register(t));
+        }
+    }
+
+    /// The load average decays every second, even if nothing runs.
+    fn next_event(&self) -> Option<i64> {
+        let second = TICKS_PER_SEC as i64;
+        Some((timer_ticks() / second + 1) * second)
+    }
+}
@@ -0,0 +1,105 @@
+//! Fixed-point Arithmetic
+//!
+//! Real numbers in 17.14 format: the lowest [`Fixed::SHIFT`] bits of an `i32`
+//! hold the fraction. Mixed operations with `i32` treat the integer as a whole
+//! number.
+
+use core::ops::{Add, Div, Mul, Sub};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Fixed(i32);
+
+impl Fixed {
+    const SHIFT: u32 = 14;
+    const ONE: i32 = 1 << Self::SHIFT;
+
+    pub const fn from_int(n: i32) -> Self {
+        Self(n * Self::ONE)
+    }
+
+    pub const fn from_bits(bits: i32) -> Self {
+        Self(bits)
+    }
+
+    pub const fn bits(self) -> i32 {
+        self.0
+    }
+
+    /// Rounds toward zero.
+    pub const fn trunc(self) -> i32 {
+        self.0 / Self::ONE
+    }
+
+    /// Rounds to the nearest integer.
+    pub const fn round(self) -> i32 {
+        if self.0 >= 0 {
+            (self.0 + Self::ONE / 2) / Self::ONE
+        } else {
+            (self.0 - Self::ONE / 2) / Self::ONE
+        }
+    }
+}
+
+impl Add for Fixed {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl Add<i32> for Fixed {
+    type Output = Self;
+
+    fn add(self, rhs: i32) -> Self {
+        self + Self::from_int(rhs)
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0 - rhs.0)
+    }
+}
+
+impl Sub<i32> for Fixed {
+    type Output = Self;
+
+    fn sub(self, rhs: i32) -> Self {
+        self - Self::from_int(rhs)
+    }
+}
+
+impl Mul for Fixed {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self(((self.0 as i64 * rhs.0 as i64) >> Self::SHIFT) as i32)
+    }
+}
+
+impl Mul<i32> for Fixed {
+    type Output = Self;
+
+    fn mul(self, rhs: i32) -> Self {
+        Self(self.0 * rhs)
+    }
+}
+
+impl Div for Fixed {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        Self((((self.0 as i64) << Self::SHIFT) / rhs.0 as i64) as i32)
+    }
+}
+
+impl Div<i32> for Fixed {
+    type Output = Self;
+
+    fn div(self, rhs: i32) -> Self {
+        Self(self.0 / rhs)
+    }
+}
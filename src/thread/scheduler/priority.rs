@@ -0,0 +1,41 @@
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+use crate::thread::scheduler::first_max;
+use crate::thread::{self, Schedule, Status, Thread};
+
+/// Priority scheduler.
+///
+/// Always picks the ready thread with the highest effective priority. Threads
+/// of equal priority are scheduled round-robin. Effective priorities may change
+/// while a thread is waiting here (through donation), so the queue is scanned
+/// on every [`schedule`](Schedule::schedule) instead of being kept sorted.
+#[derive(Default)]
+pub struct Priority(VecDeque<Arc<Thread>>);
+
+impl Priority {
+    /// Index of the earliest registered thread among those of highest priority.
+    fn highest(&self) -> Option<usize> {
+        first_max(self.0.iter().map(|t| t.effective_priority()).enumerate())
+    }
+}
+
+impl Schedule for Priority {
+    fn register(&mut self, thread: Arc<Thread>) {
+        self.0.push_back(thread)
+    }
+
+    fn schedule(&mut self) -> Option<Arc<Thread>> {
+        let index = self.highest()?;
+
+        // Keep running the current thread if no one outranks it.
+        let current = thread::current();
+        if current.status() == Status::Running
+            && current.effective_priority() > self.0[index].effective_priority()
+        {
+            return None;
+        }
+
+        self.0.remove(index)
+    }
+}
@@ -0,0 +1,75 @@
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::cmp::Reverse;
+use core::sync::atomic::Ordering::SeqCst;
+
+use crate::thread::scheduler::first_max;
+use crate::thread::{self, Schedule, Status, Thread};
+
+/// Pass increment of a thread holding a single ticket.
+const STRIDE1: u64 = 1 << 20;
+
+/// Stride scheduler.
+///
+/// A thread holds as many tickets as its effective priority, and its pass
+/// advances by `STRIDE1 / tickets` for every timer tick it runs, so the thread
+/// with the lowest pass runs next and CPU time is shared in proportion to
+/// tickets. A thread that yields or blocks before the tick isn't charged.
+/// Threads without tickets (e.g. the idle thread) only run when no one else can.
+#[derive(Default)]
+pub struct Stride {
+    ready: VecDeque<Arc<Thread>>,
+    /// Pass of the last picked thread, the virtual time of the system.
+    global_pass: u64,
+}
+
+fn tickets(thread: &Thread) -> u64 {
+    thread.effective_priority() as u64
+}
+
+impl Stride {
+    /// Index of the earliest registered thread among those of lowest pass.
+    fn lowest(&self) -> Option<usize> {
+        // Threads holding tickets come first.
+        first_max(
+            self.ready
+                .iter()
+                .map(|t| Reverse((tickets(t) == 0, t.pass.load(SeqCst))))
+                .enumerate(),
+        )
+    }
+}
+
+impl Schedule for Stride {
+    fn register(&mut self, thread: Arc<Thread>) {
+        // Time spent blocked does not count as credit.
+        thread.pass.fetch_max(self.global_pass, SeqCst);
+        self.ready.push_back(thread)
+    }
+
+    fn schedule(&mut self) -> Option<Arc<Thread>> {
+        let index = self.lowest()?;
+
+        // Keep running the current thread if it is still the furthest behind.
+        let current = thread::current();
+        let next = &self.ready[index];
+        if current.status() == Status::Running
+            && tickets(&current) > 0
+            && (tickets(next) == 0 || current.pass.load(SeqCst) <= next.pass.load(SeqCst))
+        {
+            return None;
+        }
+
+        let next = self.ready.remove(index)?;
+        self.global_pass = self.global_pass.max(next.pass.load(SeqCst));
+        Some(next)
+    }
+
+    fn tick(&mut self, current: Option<&Arc<Thread>>, _all: &[Arc<Thread>]) {
+        if let Some(current) = current {
+            if let Some(stride) = STRIDE1.checked_div(tickets(current)) {
+                current.pass.fetch_add(stride, SeqCst);
+            }
+        }
+    }
+}
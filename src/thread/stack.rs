@@ -0,0 +1,80 @@
+//! Kernel stacks
+//!
+//! A stack allocated by [`Stack::new`] has a guard page right below it, which
+//! is left unmapped in the kernel page table. Running off the bottom of the
+//! stack then faults at once, instead of silently corrupting the memory there.
+
+use crate::mem::{kalloc, kfree, KernelPgTable, PageAlign, PG_SIZE};
+use crate::smp;
+
+pub struct Stack {
+    /// Lowest address of the stack
+    bottom: usize,
+    /// Size of the stack in bytes, a multiple of [`PG_SIZE`]
+    size: usize,
+    /// Whether the stack and its guard page were allocated by us
+    guarded: bool,
+}
+
+impl Stack {
+    /// Allocates a stack of at least `size` bytes, and unmaps the page below it.
+    pub fn new(size: usize) -> Self {
+        let size = size.ceil();
+        let guard = kalloc(size + PG_SIZE, PG_SIZE) as usize;
+
+        let pte = KernelPgTable::get().get_pte_mut(guard).unwrap();
+        pte.set_invalid();
+        smp::shootdown(guard, PG_SIZE);
+
+        Self {
+            bottom: guard + PG_SIZE,
+            size,
+            guarded: true,
+        }
+    }
+
+    /// Wraps a stack set up elsewhere, such as a boot stack, which has no guard
+    /// page and is never freed.
+    ///
+    /// # Safety
+    ///
+    /// `[bottom, bottom + size)` must be memory used for nothing but this stack,
+    /// and must stay so for as long as it's in use.
+    pub unsafe fn from_raw(bottom: usize, size: usize) -> Self {
+        Self {
+            bottom,
+            size,
+            guarded: false,
+        }
+    }
+
+    pub fn bottom(&self) -> usize {
+        self.bottom
+    }
+
+    pub fn top(&self) -> usize {
+        self.bottom + self.size
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Whether `addr` is in the guard page of the stack.
+    pub fn guards(&self, addr: usize) -> bool {
+        self.guarded && (self.bottom - PG_SIZE..self.bottom).contains(&addr)
+    }
+}
+
+impl Drop for Stack {
+    fn drop(&mut self) {
+        if !self.guarded {
+            return;
+        }
+
+        let guard = self.bottom - PG_SIZE;
+        KernelPgTable::get().get_pte_mut(guard).unwrap().set_valid();
+        smp::shootdown(guard, PG_SIZE);
+        kfree(guard as *mut _, self.size + PG_SIZE, PG_SIZE);
+    }
+}
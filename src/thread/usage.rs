@@ -0,0 +1,42 @@
+//! Resource usage of threads
+
+use core::ops::AddAssign;
+
+/// Resources consumed by a thread, laid out as `rusage` in `user/lib/rusage.h`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Usage {
+    /// Timer ticks that interrupted user mode
+    pub user_ticks: usize,
+    /// Timer ticks that interrupted kernel mode
+    pub kernel_ticks: usize,
+    /// Switches away from a thread that blocked or exited
+    pub voluntary_switches: usize,
+    /// Switches away from a thread that was still runnable
+    pub involuntary_switches: usize,
+    /// Page faults, including those handled without error
+    pub page_faults: usize,
+}
+
+impl Usage {
+    /// All fields, in the order of the layout.
+    pub fn fields(&self) -> [usize; 5] {
+        [
+            self.user_ticks,
+            self.kernel_ticks,
+            self.voluntary_switches,
+            self.involuntary_switches,
+            self.page_faults,
+        ]
+    }
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, rhs: Self) {
+        self.user_ticks += rhs.user_ticks;
+        self.kernel_ticks += rhs.kernel_ticks;
+        self.voluntary_switches += rhs.voluntary_switches;
+        self.involuntary_switches += rhs.involuntary_switches;
+        self.page_faults += rhs.page_faults;
+    }
+}
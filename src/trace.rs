@@ -0,0 +1,134 @@
+//! Scheduler Event Tracing
+//!
+//! With the `trace` feature, the kernel records context switches, wakeups,
+//! blocks, priority changes and sleep lock events into a ring buffer of
+//! [`CAPACITY`] records, keeping the latest ones. The buffer is dumped on
+//! panic, or by the `trace` shell command.
+//!
+//! A dump looks like the following, one record per line:
+//!
+//! ```text
+//! [TRACE] begin 3 records, 0 dropped
+//! [TRACE] 1024 0 1 switch 2
+//! [TRACE] 1030 0 2 acquire 0xffffffc080212340
+//! [TRACE] 1052 0 2 block
+//! [TRACE] end
+//! ```
+//!
+//! where the columns are `time_us`, hart, tid, event and its argument.
+//! `tool trace` turns it into a timeline viewable in `chrome://tracing`.
+//!
+//! Without the feature, recording compiles to nothing.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::sbi::timer;
+use crate::smp;
+use crate::sync::{Lazy, Mutex, Spin};
+use crate::thread;
+
+/// Number of records kept in the buffer
+pub const CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Event {
+    /// Switched to the thread of this tid
+    Switch(isize),
+    /// Woke up the thread of this tid
+    Wakeup(isize),
+    /// Blocked itself
+    Block,
+    /// Effective priority changed to this value
+    Priority(u32),
+    /// Acquired the sleep lock at this address
+    Acquire(usize),
+    /// Released the sleep lock at this address
+    Release(usize),
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Switch(tid) => write!(f, "switch {}", tid),
+            Event::Wakeup(tid) => write!(f, "wakeup {}", tid),
+            Event::Block => write!(f, "block"),
+            Event::Priority(priority) => write!(f, "priority {}", priority),
+            Event::Acquire(lock) => write!(f, "acquire {:#x}", lock),
+            Event::Release(lock) => write!(f, "release {:#x}", lock),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Record {
+    pub time_us: usize,
+    pub hart: usize,
+    /// The thread the event happened to
+    pub tid: isize,
+    pub event: Event,
+}
+
+struct Ring {
+    records: Vec<Record>,
+    /// Number of records ever written
+    written: usize,
+}
+
+static TRACE: Lazy<Mutex<Ring, Spin>> = Lazy::new(|| {
+    Mutex::new(Ring {
+        records: Vec::with_capacity(CAPACITY),
+        written: 0,
+    })
+});
+
+/// Records `event` of the current thread.
+#[inline]
+pub fn record(event: Event) {
+    if cfg!(feature = "trace") {
+        record_of(thread::current().id(), event);
+    }
+}
+
+/// Records `event` of the thread of `tid`, overwriting the oldest record if
+/// the buffer is full.
+#[inline]
+pub fn record_of(tid: isize, event: Event) {
+    if !cfg!(feature = "trace") {
+        return;
+    }
+
+    let record = Record {
+        time_us: timer::time_us(),
+        hart: smp::hart_id(),
+        tid,
+        event,
+    };
+
+    let mut ring = TRACE.lock();
+    let index = ring.written % CAPACITY;
+    if index < ring.records.len() {
+        ring.records[index] = record;
+    } else {
+        ring.records.push(record);
+    }
+    ring.written += 1;
+}
+
+/// Prints the records in the buffer from the oldest one.
+pub fn dump() {
+    let ring = TRACE.lock();
+    let start = ring.written % ring.records.len().max(1);
+    let dropped = ring.written - ring.records.len();
+
+    kprintln!(
+        "[TRACE] begin {} records, {} dropped",
+        ring.records.len(),
+        dropped
+    );
+    let (newer, older) = ring.records.split_at(start);
+    for r in older.iter().chain(newer) {
+        kprintln!("[TRACE] {} {} {} {}", r.time_us, r.hart, r.tid, r.event);
+    }
+    kprintln!("[TRACE] end");
+}
@@ -4,6 +4,8 @@
 mod pagefault;
 mod syscall;
 
+pub use self::pagefault::STACK_LIMIT;
+
 use crate::device::{plic, virtio};
 use crate::sbi;
 use crate::thread;
@@ -20,8 +22,10 @@ use riscv::register::*;
 #[repr(C)]
 
 /// Trap context
+#[derive(Clone)]
 pub struct Frame {
-    /// General regs[0..31].
+    /// General regs[0..31]. For user traps, `x[0]` keeps the hart pointer (`tp`)
+    /// of the kernel.
     pub x: [usize; 32],
     /// CSR sstatus.
     pub sstatus: Sstatus,
@@ -59,16 +63,26 @@ pub extern "C" fn trap_handler(frame: &mut Frame) {
     match scause {
         Exception(UserEnvCall) => {
             let id = frame.x[17];
-            let args = [frame.x[10], frame.x[11], frame.x[12]];
+            let args = [
+                frame.x[10], frame.x[11], frame.x[12], frame.x[13], frame.x[14], frame.x[15],
+            ];
             #[cfg(feature = "debug")]
             kprintln!("[TRAP] User ECall, ID={}, args={:?}", id, args);
             unsafe { riscv::register::sstatus::set_sie() };
             // Increase sepc by 1 to skip ecall.
             frame.sepc += 4;
-            frame.x[10] = syscall::syscall_handler(id, args) as usize;
+            frame.x[10] = syscall::syscall_handler(id, args, frame) as usize;
         }
 
         Interrupt(SupervisorTimer) => {
+            {
+                let current = thread::current();
+                let mut usage = current.usage.lock();
+                match frame.sstatus.spp() {
+                    SPP::User => usage.user_ticks += 1,
+                    SPP::Supervisor => usage.kernel_ticks += 1,
+                }
+            }
             sbi::timer::tick();
             unsafe { riscv::register::sstatus::set_sie() };
             thread::schedule();
@@ -97,6 +111,7 @@ pub extern "C" fn trap_handler(frame: &mut Frame) {
         Exception(f @ LoadPageFault)
         | Exception(f @ StorePageFault)
         | Exception(f @ InstructionPageFault) => {
+            thread::current().usage.lock().page_faults += 1;
             pagefault::handler(frame, f, stval);
         }
 
@@ -110,6 +125,11 @@ pub extern "C" fn trap_handler(frame: &mut Frame) {
         }
     }
 
+    // A killed process must not get back to user mode.
+    if frame.sstatus.spp() == SPP::User && thread::current().is_killed() {
+        crate::userproc::exit_killed();
+    }
+
     #[cfg(feature = "debug")]
     kprintln!("[TRAP] exit");
 }
@@ -189,6 +209,10 @@ arch::global_asm! {r#"
         sd t1, 33*8(sp)
         sd t2,  2*8(sp)  # save to `x2`
 
+    # (2.4) Restore the hart pointer, which the user may have overwritten.
+    # It's kept in the unused `x0` slot by `trap_exit_u`.
+        ld tp,  0*8(sp)
+
     # (3) Call trap handler.
     # Must use `call`.
         mv   a0, sp   # pass frame
@@ -205,6 +229,10 @@ arch::global_asm! {r#"
 
     trap_exit_u:
 
+    # (0) Keep the hart pointer for the next `trap_entry_u`. The thread may have
+    # been moved to another hart since its last entry.
+        sd tp,  0*8(sp)
+
     # (1) Restore CSR.
     # TODO: should we restore `stvec` here?
         ld   t0, 32*8(sp)
@@ -287,7 +315,15 @@ arch::global_asm! {r#"
     # https://five-embeddev.com/riscv-isa-manual/latest/supervisor.html#supervisor-trap-vector-base-address-register-stvec
 
     trap_entry_k:
+    # Make sure the frame fits in the kernel stack. Pushing it into the guard
+    # page below would fault again and again.
+        sd t0, {scratch}(tp)
+        ld t0, {stack_limit}(tp)
         addi sp, sp, -34*8
+        bgeu sp, t0, 1f
+        j trap_stack_overflow
+    1:
+        ld t0, {scratch}(tp)
 
     # save general-purpose registers
         # sd x0, 0*8(sp)
@@ -354,7 +390,7 @@ arch::global_asm! {r#"
         ld x1,   1*8(sp)
         # ld x2, 2*8(sp)
         ld x3,   3*8(sp)
-        ld x4,   4*8(sp)
+        # ld x4, 4*8(sp)  # keep the hart pointer, in case of migration
         ld x5,   5*8(sp)
         ld x6,   6*8(sp)
         ld x7,   7*8(sp)
@@ -385,4 +421,34 @@ arch::global_asm! {r#"
 
         addi sp, sp, 34*8
         sret
-"#}
+
+    # Report the overflow on the emergency stack of this hart. There is no way back.
+    trap_stack_overflow:
+        ld t0, {id}(tp)
+        addi t0, t0, 1
+        slli t0, t0, {overflow_stack_shift}
+        la sp, overflow_stacks
+        add sp, sp, t0
+        call trap_stack_overflow_handler
+
+    .section .bss
+    .align 12
+    overflow_stacks:
+        .space {overflow_stacks_size}
+"#,
+    id = const crate::smp::LOCAL_ID,
+    scratch = const crate::smp::LOCAL_SCRATCH,
+    stack_limit = const crate::smp::LOCAL_STACK_LIMIT,
+    overflow_stack_shift = const OVERFLOW_STACK_SHIFT,
+    overflow_stacks_size = const (1 << OVERFLOW_STACK_SHIFT) * crate::smp::MAX_HARTS,
+}
+
+/// Each hart has an emergency stack of `1 << OVERFLOW_STACK_SHIFT` bytes
+const OVERFLOW_STACK_SHIFT: usize = 13;
+
+/// Called by `trap_entry_k` on the emergency stack, when the trap frame doesn't
+/// fit in the kernel stack.
+#[no_mangle]
+extern "C" fn trap_stack_overflow_handler() -> ! {
+    pagefault::stack_overflow(sepc::read(), stval::read())
+}
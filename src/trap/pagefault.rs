@@ -9,8 +9,11 @@ use crate::userproc;
 
 use crate::fs::disk::Swap;
 use crate::io::SeekFrom::Start;
+use crate::mem::pagecache::PageCache;
 use crate::mem::pagetable::PTEFlags;
-use crate::mem::palloc::UserPool;
+use crate::mem::palloc::frame::GlobalFrameTable;
+use crate::mem::palloc::replacement::{Stats, STATS};
+use crate::mem::palloc::{self, UserPool};
 use crate::mem::{PageAlign, PhysAddr, PG_SIZE};
 use crate::thread::STACK_TOP;
 use mem::PG_SHIFT;
@@ -22,14 +25,36 @@ use riscv::register::sstatus::{self, SPP};
 
 pub const STACK_LIMIT: usize = 0x800000;
 
+/// A page from the user pool, or `None` if memory is exhausted.
+fn alloc_page() -> Option<*mut u8> {
+    let page = unsafe { UserPool::alloc_pages(1) };
+    (!page.is_null()).then_some(page)
+}
+
+/// Kills the current process, for which no memory is left. It exits at its
+/// next safe point. Returns `false`, as the fault isn't handled.
+fn out_of_memory() -> bool {
+    kprintln!("User thread {} killed: out of memory.", current().name());
+    current().kill();
+    false
+}
+
 pub fn stack_growth_handler(frame: &Frame, addr: usize, user_mode: bool) -> bool {
     let sp = frame.x[2];
     if addr >= STACK_TOP || addr < STACK_TOP - STACK_LIMIT || addr < sp {
         return false;
     } // not in stack / below sp
-    let mut current_pt = unsafe { PageTable::effective_pagetable() };
+    let current = current();
+    let Some(pagetable) = current.pagetable.as_ref() else {
+        return false;
+    };
+    let Some(page) = alloc_page() else {
+        return out_of_memory();
+    };
+    Stats::count(&STATS.faults);
+    let mut current_pt = pagetable.lock();
     current_pt.map(
-        PhysAddr::from( unsafe { UserPool::alloc_pages(1) } ),
+        PhysAddr::from(page),
         PageAlign::floor(addr),
         PG_SIZE,
         PTEFlags::V | PTEFlags::R | PTEFlags::W | PTEFlags::U,
@@ -40,23 +65,36 @@ pub fn stack_growth_handler(frame: &Frame, addr: usize, user_mode: bool) -> bool
 
 pub fn spt_handler(frame: &Frame, va: usize) -> bool {
     let current = current();
-    let spt = current.supplementary_pagetable.lock();
-    if let Some(mapinfo) = spt.list.iter().find(|m| m.contains(va)).map(|m| m.clone()) {
-        let pos = (va - mapinfo.va).floor();
-        let mut pt = unsafe { PageTable::effective_pagetable() };
+    let mut spt = current.supplementary_pagetable.lock();
+    if let Some(pos) = spt.list.iter().position(|m| m.contains(va)) {
+        let mapinfo = spt.list[pos].clone();
         spt.release();
-        let start_va = unsafe { UserPool::alloc_pages(1) as usize };
-        let start_pa = PhysAddr::from(start_va);
+        let Some(start_va) = alloc_page() else {
+            spt.acquire();
+            return out_of_memory();
+        };
+        let start_pa = PhysAddr::from(start_va as usize);
         let buf = unsafe { (start_va as *mut [u8; PG_SIZE]).as_mut().unwrap() };
-        let size = Swap::read_page(pos + mapinfo.offset, &mut buf[..PG_SIZE]);
+        let Ok(size) = Swap::read_page(mapinfo.offset, &mut buf[..PG_SIZE]) else {
+            unsafe { UserPool::dealloc_pages(start_va, 1) };
+            spt.acquire();
+            return false;
+        };
         buf[size..].fill(0);
         spt.acquire();
+        Stats::count(&STATS.faults);
+        Stats::count(&STATS.swapins);
 
+        // The slot is given back, so the page is mapped dirty to be written
+        // out again when evicted.
+        spt.list.remove(pos);
+        Swap::free_page(mapinfo.offset);
+        let mut pt = current.pagetable.as_ref().unwrap().lock();
         pt.map(
             start_pa,
             va.floor(),
             PG_SIZE,
-            mapinfo.flags | PTEFlags::V | PTEFlags::A,
+            mapinfo.flags | PTEFlags::V | PTEFlags::A | PTEFlags::D,
         );
         pt.activate();
         true
@@ -69,32 +107,53 @@ pub fn mmap_handler(frame: &Frame, va: usize) -> bool {
     let current = current();
     let mapping_table = current.mapping_table.lock();
     if let Some(mut mapinfo) = mapping_table.list.iter().find(|m| m.contains(va)).map(|m| m.clone()) {
+        Stats::count(&STATS.faults);
         let pos = (va - mapinfo.va).floor();
         mapping_table.release();
-        mapinfo
-            .file
-            .as_mut()
-            .unwrap()
-            .seek(Start(pos + mapinfo.offset))
-            .unwrap();
+        let limit = (mapinfo.filesize.max(pos) - pos).min(PG_SIZE);
+        let flags = mapinfo.flags | PTEFlags::V | PTEFlags::A;
 
-        let mut current_pt = unsafe { PageTable::effective_pagetable() };
+        // Read-only segments of the executable are shared by the page cache.
+        let key = (mapinfo.mapid == -1 && !mapinfo.flags.contains(PTEFlags::W))
+            .then(|| (mapinfo.file.as_ref().unwrap().inum(), pos + mapinfo.offset));
+        if let Some(pa) = key.and_then(|(inum, offset)| PageCache::get(inum, offset, limit)) {
+            mapping_table.acquire();
+            let mut current_pt = current.pagetable.as_ref().unwrap().lock();
+            // It's never evicted while shared, no need to track it.
+            current_pt.map_no_update(pa, va.floor(), PG_SIZE, flags);
+            current_pt.activate();
+            return true;
+        }
 
-        let start_va = unsafe { UserPool::alloc_pages(1) as usize };
+        let Some(page) = alloc_page() else {
+            mapping_table.acquire();
+            return out_of_memory();
+        };
+        let start_va = page as usize;
         let start_pa = PhysAddr::from(start_va);
         let buf = unsafe { (start_va as *mut [u8; PG_SIZE]).as_mut().unwrap() };
-        let limit = (mapinfo.filesize.max(pos) - pos).min(PG_SIZE);
 
-        let size = mapinfo.file.unwrap().read(&mut buf[..limit]).unwrap();
+        // Anonymous pages are all zeros.
+        let size = match mapinfo.file.as_mut() {
+            Some(file) => {
+                file.seek(Start(pos + mapinfo.offset)).unwrap();
+                file.read(&mut buf[..limit]).unwrap()
+            }
+            None => 0,
+        };
         buf[size..].fill(0);
+        let pa = match key {
+            Some((inum, offset)) => PageCache::insert(inum, offset, limit, start_pa),
+            None => start_pa,
+        };
         mapping_table.acquire();
 
-        current_pt.map(
-            start_pa,
-            va.floor(),
-            PG_SIZE,
-            mapinfo.flags | PTEFlags::V | PTEFlags::A,
-        );
+        let mut current_pt = current.pagetable.as_ref().unwrap().lock();
+        if pa == start_pa {
+            current_pt.map(start_pa, va.floor(), PG_SIZE, flags);
+        } else {
+            current_pt.map_no_update(pa, va.floor(), PG_SIZE, flags);
+        }
         current_pt.activate();
         true
     } else {
@@ -102,19 +161,93 @@ pub fn mmap_handler(frame: &Frame, va: usize) -> bool {
     }
 }
 
+/// Gives the current process its own copy of the copy-on-write page at `va`,
+/// which it is writing to for the first time since a fork.
+pub fn cow_handler(va: usize) -> bool {
+    let current = current();
+    let pagetable = current.pagetable.as_ref().unwrap();
+
+    let shared = match pagetable.lock().get_pte(va) {
+        Some(entry) if entry.is_valid() && entry.is_cow() => {
+            GlobalFrameTable::is_shared(entry.pa().value())
+        }
+        _ => return false,
+    };
+    // Allocating may evict pages, which can't be done under the lock.
+    let page = match shared.then(alloc_page) {
+        Some(None) => return out_of_memory(),
+        page => page.flatten(),
+    };
+
+    let mut pt = pagetable.lock();
+    let entry = match pt.get_pte_mut(va) {
+        Some(entry) if entry.is_valid() && entry.is_cow() => entry,
+        // Evicted in the meantime, let the retried access fault it in.
+        _ => {
+            page.map(|page| unsafe { UserPool::dealloc_pages(page, 1) });
+            return true;
+        }
+    };
+
+    let old = entry.pa();
+    match page {
+        Some(page) => {
+            unsafe { core::ptr::copy_nonoverlapping(old.into_va() as *const u8, page, PG_SIZE) };
+
+            let mut flags = entry.flag();
+            flags.remove(PTEFlags::COW);
+            pt.map(PhysAddr::from(page), va.floor(), PG_SIZE, flags | PTEFlags::W);
+            unsafe { UserPool::dealloc_pages(old.into_va() as *mut _, 1) };
+        }
+        // The others have made their copies already.
+        None => entry.clear_cow(),
+    }
+    pt.activate();
+    true
+}
+
+/// Reports that the current thread ran off its kernel stack at `pc`, while
+/// accessing `addr`.
+pub fn stack_overflow(pc: usize, addr: usize) -> ! {
+    panic!(
+        "stack overflow in thread {}, pc={:#x}, addr={:#x}",
+        current().name(),
+        pc,
+        addr
+    );
+}
+
 pub fn handler(frame: &mut Frame, fault: Exception, addr: usize) {
     let privilege = frame.sstatus.spp();
 
-    let present = {
+    if privilege == SPP::Supervisor && current().stack().guards(addr) {
+        stack_overflow(frame.sepc, addr);
+    }
+
+    let is_present = || {
         let table = unsafe { PageTable::effective_pagetable() };
         match table.get_pte(addr) {
             Some(entry) => entry.is_valid(),
             None => false,
         }
     };
+    let present = is_present();
 
     unsafe { sstatus::set_sie() };
 
+    // The page may be on its way out, and can only be found once it's out. If
+    // the eviction was given up, it's back already.
+    if !present {
+        drop(palloc::pause_evictions());
+        if is_present() {
+            return;
+        }
+    }
+
+    if present && fault == StorePageFault && cow_handler(addr) {
+        return;
+    }
+
     match privilege {
         SPP::Supervisor => {
             if !present {
@@ -148,11 +281,14 @@ pub fn handler(frame: &mut Frame, fault: Exception, addr: usize) {
                     return;
                 }
             }
-            kprintln!(
-                "User thread {} dying due to page fault.",
-                thread::current().name()
-            );
-            userproc::exit(-1);
+            // Out of memory has been reported already.
+            if !thread::current().is_killed() {
+                kprintln!(
+                    "User thread {} dying due to page fault.",
+                    thread::current().name()
+                );
+            }
+            userproc::exit_killed();
         }
     }
 }
@@ -12,11 +12,13 @@ use alloc::vec::Vec;
 
 use crate::alloc::vec;
 use crate::fs::File;
-use crate::mem::palloc::UserPool;
 use crate::mem::userbuf::{self, read_user_byte, read_user_usize, write_user_byte, write_user_usize};
+use crate::mem::mappingtable::MapInfo;
 use crate::mem::{PTEFlags, PageTable, PG_SHIFT, PG_SIZE};
 use crate::sbi::console;
+use crate::sbi::timer::TICKS_PER_SEC;
 use crate::thread::current;
+use crate::trap::Frame;
 
 use crate::{
     OsError,
@@ -25,7 +27,7 @@ use crate::{
     mem::{PG_MASK, in_kernel_space, pagetable},
     sbi::{console_getchar, shutdown},
     thread,
-    userproc::{self, execute}
+    userproc::{self, execute, futex, mmap}
 };
 
 const SYS_HALT:     usize = 1;
@@ -44,6 +46,24 @@ const SYS_FSTAT:    usize = 12;
 const SYS_MMAP:     usize = 13;
 const SYS_MUNMAP:   usize = 14;
 
+const SYS_NICE:     usize = 17;
+const SYS_GETNICE:  usize = 18;
+const SYS_SLEEP:    usize = 19;
+const SYS_GETRUSAGE: usize = 20;
+const SYS_KILL:     usize = 21;
+const SYS_WAITPID:  usize = 22;
+const SYS_FUTEX_WAIT: usize = 23;
+const SYS_FUTEX_WAKE: usize = 24;
+const SYS_FORK:     usize = 25;
+const SYS_EXECVE:   usize = 26;
+const SYS_BRK:      usize = 27;
+const SYS_SBRK:     usize = 28;
+const SYS_MMAP2:    usize = 29;
+const SYS_MUNMAP2:  usize = 30;
+
+const RUSAGE_SELF:      isize = 0;
+const RUSAGE_CHILDREN:  isize = -1;
+
 const O_RDONLY:     usize = 0;
 const O_WRONLY:     usize = 0x001;
 const O_RDWR:       usize = 0x002;
@@ -62,6 +82,18 @@ fn valid_ptr(ptr: usize) -> bool {
         }
 }
 
+/// Reads a null-terminated array of strings, like `argv`.
+fn ptr2argv(mut ptr: usize) -> Option<Vec<String>> {
+    let mut argv = Vec::new();
+    loop {
+        match read_user_usize(ptr as *const usize).ok()? {
+            0 => return Some(argv),
+            arg => argv.push(ptr2string(arg)?),
+        }
+        ptr += core::mem::size_of::<usize>();
+    }
+}
+
 fn ptr2string(mut ptr: usize) -> Option<String> {
     let mut str = String::new();
 
@@ -87,10 +119,15 @@ fn ptr2string(mut ptr: usize) -> Option<String> {
     }
 }
 
-pub fn syscall_handler(id: usize, args: [usize; 3]) -> isize {
+/// Handles syscall `id`. `frame` is the trap context of the caller, with `sepc`
+/// past the `ecall` already.
+pub fn syscall_handler(id: usize, args: [usize; 6], frame: &mut Frame) -> isize {
     // TODO: LAB2 impl
     match id {
         SYS_HALT => {
+            #[cfg(feature = "mem-stats")]
+            crate::mem::palloc::replacement::STATS.dump();
+            DISKFS.unmount();
             shutdown();
         }
         SYS_EXIT => {
@@ -108,26 +145,9 @@ pub fn syscall_handler(id: usize, args: [usize; 3]) -> isize {
                     _ => return -1,
                 };
 
-                let mut argv = Vec::new();
-                let mut ptr = args[1];
-                loop {
-                    // if !valid_ptr(ptr) {
-                    //     break -1;
-                    // }
-                    let val = match read_user_usize(ptr as *const usize) {
-                        Ok(val) => val,
-                        _ => return -1,
-                    };
-                    if val == 0 {
-                        break execute(file, argv);
-                    }
-                    let str = match ptr2string(val) {
-                        Some(str) => str,
-                        _ => break -1,
-                    };
-                    argv.push(str);
-
-                    ptr += core::mem::size_of::<usize>();
+                match ptr2argv(args[1]) {
+                    Some(argv) => execute(file, argv),
+                    _ => return -1,
                 }
             };
             {
@@ -360,6 +380,7 @@ pub fn syscall_handler(id: usize, args: [usize; 3]) -> isize {
             current.fdlist.lock().list.retain(|x| x.fd != fd);
             0
         }
+        // Kept for the older programs, SYS_MMAP2 and SYS_MUNMAP2 supersede it.
         SYS_MMAP => {
             let fd = args[0] as isize;
             let va = args[1];
@@ -413,58 +434,144 @@ pub fn syscall_handler(id: usize, args: [usize; 3]) -> isize {
                 return -1; // already mapped
             }
 
-            thread::current()
-                .mapping_table
-                .lock()
-                .map(file.clone(), 0, va, size, size, flags)
+            let mut mapinfo = MapInfo::new(0, Some(file.clone()), 0, va, size, size, flags);
+            mapinfo.shared = true;
+            thread::current().mapping_table.lock().map(mapinfo)
         }
         SYS_MUNMAP => {
             let mapid = args[0] as isize;
             if mapid < 0 {
                 return -1; // invalid mapping id
             }
-            let pt = unsafe { PageTable::effective_pagetable() };
+            mmap::unmap(mapid);
+            0
+        }
+        SYS_NICE => {
+            thread::set_nice(args[0] as i32);
+            0
+        }
+        SYS_GETNICE => thread::get_nice() as isize,
+        SYS_SLEEP => {
+            // Round up, so that we never sleep less than asked. The duration
+            // is unchecked, so saturate rather than overflow.
+            let ms = args[0] as isize as i64;
+            let tps = TICKS_PER_SEC as i64;
+            thread::sleep(ms.saturating_mul(tps).saturating_add(999) / 1000);
+            0
+        }
+        SYS_GETRUSAGE => {
             let current = thread::current();
-            let mut mapping_table = current
-                .mapping_table
-                .lock();
-            let mapinfo = match mapping_table
-                .list
-                .iter_mut()
-                .find(|mapinfo| mapinfo.mapid == mapid) {
-                    Some(mapinfo) => mapinfo,
-                    _ => return 0,
-                };
-            for i in (0..mapinfo.memsize).step_by(PG_SIZE) {
-                if let Some(entry) = pt.get_pte_mut(mapinfo.va + i) {
-                    if entry.is_valid() && entry.is_dirty() {
-                        mapinfo
-                            .file
-                            .as_mut()
-                            .unwrap()
-                            .seek(SeekFrom::Start((mapinfo.offset + i) as usize))
-                            .unwrap();
-                        let size = (mapinfo.memsize - i).min(PG_SIZE);
-
-                        let buf = unsafe {
-                            ((mapinfo.va + i) as *const [u8; PG_SIZE]).as_ref().unwrap()
-                        };
-                        mapinfo.file.as_mut().unwrap().write(&buf[..size]).unwrap();
+            let usage = match args[0] as isize {
+                RUSAGE_SELF => *current.usage.lock(),
+                RUSAGE_CHILDREN => *current.children_usage.lock(),
+                _ => return -1,
+            };
+
+            let ptr = args[1];
+            for (i, field) in usage.fields().iter().enumerate() {
+                let field_ptr = ptr + i * core::mem::size_of::<usize>();
+                if write_user_usize(field_ptr as *const usize, *field).is_err() {
+                    return -1;
+                }
+            }
+            0
+        }
+        SYS_KILL => match userproc::kill(args[0] as isize) {
+            Ok(()) => 0,
+            Err(_) => -1,
+        },
+        SYS_WAITPID => {
+            // Returns the pid once the child exits, or 0 on timeout. A negative
+            // timeout waits forever.
+            let pid = args[0] as isize;
+            let status = args[1] as *const u8;
+            let ms = args[2] as isize as i64;
+
+            // Check the status pointer before the exit value is consumed. It's
+            // the exit value, and whether the child was killed.
+            let write_status = |value: i32, killed: bool| {
+                value
+                    .to_le_bytes()
+                    .iter()
+                    .chain((killed as i32).to_le_bytes().iter())
+                    .enumerate()
+                    .all(|(i, b)| write_user_byte(status.wrapping_add(i), *b).is_ok())
+            };
+            if !status.is_null() && !write_status(0, false) {
+                return -1;
+            }
+
+            let tps = TICKS_PER_SEC as i64;
+            let ticks = (ms >= 0).then(|| ms.saturating_mul(tps).saturating_add(999) / 1000);
+            match userproc::wait_timeout(pid, ticks) {
+                Some(Some((value, killed))) => {
+                    if !status.is_null() {
+                        write_status(value as i32, killed);
                     }
-                    unsafe {
-                        UserPool::dealloc_pages((entry.pa().into_va()) as *mut _, 1);
+                    pid
+                }
+                Some(None) => 0,
+                None => -1,
+            }
+        }
+        SYS_FUTEX_WAIT => match futex::wait(args[0], args[1] as u32) {
+            Ok(()) => 0,
+            Err(_) => -1,
+        },
+        SYS_FUTEX_WAKE => match futex::wake(args[0], args[1]) {
+            Ok(n) => n as isize,
+            Err(_) => -1,
+        },
+        SYS_FORK => userproc::fork(frame),
+        SYS_EXECVE => {
+            let name = match ptr2string(args[0]) {
+                Some(name) => name,
+                _ => return -1,
+            };
+            let file = match DISKFS.open(name.as_str().into()) {
+                Ok(f) => f,
+                _ => return -1,
+            };
+            match ptr2argv(args[1]) {
+                Some(argv) => userproc::execve(file, argv, frame),
+                _ => -1,
+            }
+        }
+        SYS_BRK => match userproc::brk(args[0]) {
+            Ok(_) => 0,
+            Err(_) => -1,
+        },
+        SYS_SBRK => match userproc::sbrk(args[0] as isize) {
+            Ok(old) => old as isize,
+            Err(_) => -1,
+        },
+        SYS_MMAP2 => {
+            let (addr, len, prot, flags, fd, offset) =
+                (args[0], args[1], args[2], args[3], args[4] as isize, args[5]);
+            let file = if flags & mmap::MAP_ANONYMOUS != 0 {
+                None
+            } else {
+                if fd >= 0 && fd <= 2 {
+                    return -1; // not mappable
+                }
+                // The file must be readable, and writable to write back to it.
+                let writeback = flags & mmap::MAP_SHARED != 0 && prot & mmap::PROT_WRITE != 0;
+                match thread::current().fdlist.lock().get_by_fd(fd) {
+                    Some(x) if x.flag & O_WRONLY == 0 && (!writeback || x.flag & O_RDWR != 0) => {
+                        Some(x.file.clone())
                     }
-                    entry.set_invalid();
+                    _ => return -1,
                 }
+            };
+            match mmap::mmap(addr, len, prot, flags, file, offset) {
+                Ok(va) => va as isize,
+                Err(_) => -1,
             }
-            pt.activate();
-            thread::current()
-                .mapping_table
-                .lock()
-                .list
-                .retain(|mapinfo| mapinfo.mapid != mapid);
-            0
         }
+        SYS_MUNMAP2 => match mmap::munmap(args[0], args[1]) {
+            Ok(()) => 0,
+            Err(_) => -1,
+        },
         _ => {
             panic!("unknown syscall");
         }
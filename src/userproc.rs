@@ -1,9 +1,12 @@
 //! User process.
 //!
 
+pub mod futex;
 mod load;
+pub mod mmap;
 
 use alloc::string::String;
+use alloc::vec;
 use alloc::vec::Vec;
 use core::arch::asm;
 use core::mem::MaybeUninit;
@@ -14,12 +17,15 @@ use riscv::register::sstatus;
 
 use crate::fs::File;
 use crate::mem::{PageAlign, PageTable, PhysAddr, PG_SIZE};
+use crate::mem::mappingtable::{MapInfo, MappingTable};
 use crate::mem::pagetable::KernelPgTable;
+use crate::mem::palloc::frame::GlobalFrameTable;
 use crate::sbi::interrupt;
-use crate::{childinfo, sbi};
-use crate::sync::Semaphore;
-use crate::thread::{self, Manager, manager};
-use crate::trap::{trap_exit_u, Frame};
+use crate::{childinfo, sbi, OsError, Result};
+use crate::sync::{Mutex, Semaphore};
+use crate::thread::{self, Manager, Thread, manager};
+use crate::thread::STACK_TOP;
+use crate::trap::{trap_exit_u, Frame, STACK_LIMIT};
 
 use core::convert::TryInto;
 use core::ptr::write_bytes;
@@ -30,17 +36,22 @@ use crate::childinfo::ChildInfo;
 use crate::thread::{current, schedule};
 
 use crate::fs::disk::Swap;
-use crate::io::{Seek, SeekFrom, Write};
-use crate::mem::palloc::UserPool;
+use crate::mem::palloc::{self, UserPool};
+
+/// Exit value of a process killed by the kernel or by [`kill`]. A process may
+/// exit with it by itself too, [`wait_timeout`] tells them apart.
+pub const KILLED_EXIT: isize = -1;
 
 pub struct UserProc {
-    #[allow(dead_code)]
-    bin: File,
+    /// The running executable, replaced by [`execve`]
+    bin: Mutex<File>,
 }
 
 impl UserProc {
     pub fn new(file: File) -> Self {
-        Self { bin: file }
+        Self {
+            bin: Mutex::new(file),
+        }
     }
 }
 
@@ -58,16 +69,77 @@ pub fn execute(mut file: File, argv: Vec<String>) -> isize {
         argv
     );
 
+    let (pt, mappingtable, frame) = match load_image(&mut file, &argv) {
+        Ok(x) => x,
+        Err(_) => return -1,
+    };
+
+    // Here the new process will be created.
+    let userproc = UserProc::new(file);
+
+    let handle = thread::Builder::new(move || start(frame))
+        .pagetable(pt)
+        .userproc(userproc)
+        .parent(thread::current())
+        .set_mapping_table(mappingtable)
+        .spawn();
+
+    let child = handle.thread();
+    let childinfo = child.init_child_info();
+    thread::current().children.lock().push(childinfo);
+    child.id()
+}
+
+/// Replaces the image of the current process with an object file, keeping its
+/// pid and its open files except those opened with [`O_CLOEXEC`](crate::fdlist::O_CLOEXEC). `frame` is
+/// the trap context of the syscall, which becomes the new image's initial one.
+///
+/// ## Return
+/// - `-1`: On error, in which case the old image is left intact.
+/// - `argc`: On success, which is the `a0` of the new image.
+pub fn execve(mut file: File, argv: Vec<String>, frame: &mut Frame) -> isize {
+    let (pt, mappingtable, new_frame) = match load_image(&mut file, &argv) {
+        Ok(x) => x,
+        Err(_) => return -1,
+    };
+
+    let current = thread::current();
+    release_memory(&current);
+    let mut old = {
+        let mut pagetable = current.pagetable.as_ref().expect("not a user process").lock();
+        let old = core::mem::replace(&mut *pagetable, pt);
+        pagetable.activate();
+        old
+    };
+    // Frames of the old image are no longer ours, wherever they go next.
+    old.for_each_user_page(|_, entry| GlobalFrameTable::destroy(entry.pa().value()));
+    unsafe { old.destroy() };
+    // Files of the old mappings are closed once the lock is released.
+    let old_mappings = core::mem::replace(&mut *current.mapping_table.lock(), mappingtable);
+    drop(old_mappings);
+    current.supplementary_pagetable.lock().list.clear();
+    current.fdlist.lock().close_on_exec();
+    *current.userproc.as_ref().unwrap().bin.lock() = file;
+
+    let sstatus = frame.sstatus;
+    *frame = new_frame;
+    frame.sstatus = sstatus;
+    frame.x[10] as isize
+}
+
+/// Builds an address space for an object file, with `argv` pushed onto its
+/// stack. Returns it along with its mappings and the initial trap frame.
+fn load_image(file: &mut File, argv: &[String]) -> Result<(PageTable, MappingTable, Frame)> {
     // It only copies L2 pagetable. This approach allows the new thread
     // to access kernel code and data during syscall without the need to
     // switch pagetables.
     let mut pt = KernelPgTable::clone();
 
-    let (exec_info, mappingtable) = match load::load_executable(&mut file, &mut pt) {
+    let (exec_info, mappingtable) = match load::load_executable(file, &mut pt) {
         Ok(x) => x,
-        Err(_) => unsafe {
+        Err(e) => unsafe {
             pt.destroy();
-            return -1;
+            return Err(e);
         },
     };
 
@@ -81,22 +153,29 @@ pub fn execute(mut file: File, argv: Vec<String>) -> isize {
     let mut frame = unsafe { MaybeUninit::<Frame>::zeroed().assume_init() };
     frame.sepc = exec_info.entry_point;
 
-    // Here the new process will be created.
-    let userproc = UserProc::new(file);
-
-    // TODO: (Lab2) Pass arguments to user program
+    match push_args(stack_va, exec_info.init_sp, argv, &mut frame) {
+        Ok(()) => Ok((pt, mappingtable, frame)),
+        Err(e) => unsafe {
+            pt.destroy();
+            Err(e)
+        },
+    }
+}
 
+/// Pushes `argv` onto the user stack page at `stack_va`, whose top is
+/// `init_sp` for the user, and sets up `sp`, `argc` and `argv` in `frame`.
+fn push_args(stack_va: usize, init_sp: usize, argv: &[String], frame: &mut Frame) -> Result<()> {
     const LEN_BYTE: usize = core::mem::size_of::<usize>();
 
     let mut sp = stack_va + 4096;
-    let offset = exec_info.init_sp - sp;
+    let offset = init_sp - sp;
     let mut arg_ptrs = Vec::new();
     for arg in argv.iter() {
         let bytes = (arg.as_bytes().len() / LEN_BYTE + 1) * LEN_BYTE;
         sp -= bytes;
 
         if sp < stack_va {
-            return -1;
+            return Err(OsError::ArgumentTooLong);
         }
 
         unsafe {
@@ -112,13 +191,13 @@ pub fn execute(mut file: File, argv: Vec<String>) -> isize {
 
     sp -= LEN_BYTE; // for null pointer
     if sp < stack_va {
-        return -1;
+        return Err(OsError::ArgumentTooLong);
     }
     unsafe { write_bytes(sp as *mut u8, 0, 1) }
 
     sp -= arg_ptrs.len() * LEN_BYTE;
     if sp < stack_va {
-        return -1;
+        return Err(OsError::ArgumentTooLong);
     }
     unsafe {
         copy_nonoverlapping(arg_ptrs.as_ptr(), sp as *mut usize, arg_ptrs.len());
@@ -126,7 +205,7 @@ pub fn execute(mut file: File, argv: Vec<String>) -> isize {
 
     sp -= LEN_BYTE; // for return address
     if sp < stack_va {
-        return -1;
+        return Err(OsError::ArgumentTooLong);
     }
     unsafe { write_bytes(sp as *mut u8, 0, 1) }
 
@@ -136,16 +215,92 @@ pub fn execute(mut file: File, argv: Vec<String>) -> isize {
     frame.x[2]  = sp + offset; // sp
     frame.x[10] = argc; // a0
     frame.x[11] = argv; // a1
+    Ok(())
+}
+
+/// Forks the current process. The child returns from the same syscall as the
+/// one in `frame`, but with 0. Its address space is a copy of ours, whose
+/// writable pages are shared copy-on-write, and it inherits our open files.
+/// Pages of shared mappings in memory are shared for real.
+///
+/// ## Return
+/// - `tid`: Tid of the child.
+/// - `-1`: There is no swap left for its copy of our swapped out pages.
+pub fn fork(frame: &Frame) -> isize {
+    let current = thread::current();
+
+    // Share all mapped pages, write-protecting the writable ones on both sides,
+    // except those of shared mappings, which both keep writing to.
+    let mut pt = KernelPgTable::clone();
+    // Registered in the frame table once the child exists.
+    let mut frames = Vec::new();
+    {
+        let mapping_table = current.mapping_table.lock();
+        let shared: Vec<_> = mapping_table.list.iter().filter(|m| m.shared).collect();
+        let parent_pt = current.pagetable.as_ref().expect("not a user process").lock();
+        parent_pt.for_each_user_page(|va, entry| {
+            if entry.is_writable() && !shared.iter().any(|m| m.contains(va)) {
+                entry.set_cow();
+            }
+            GlobalFrameTable::share(entry.pa().value());
+            pt.map_no_update(entry.pa(), va, PG_SIZE, entry.flag());
+            frames.push((entry.pa().value(), va, entry.flag()));
+        });
+        parent_pt.activate();
+    }
 
-    let child = thread::Builder::new(move || start(frame))
+    let mappingtable = MappingTable {
+        list: current.mapping_table.lock().list.iter().map(MapInfo::reopen).collect(),
+    };
+
+    // Swapped out pages get their own slots. Our pages are all shared now, so
+    // only an eviction in progress may still add to them.
+    let mut spt = MappingTable::new();
+    let paused = palloc::pause_evictions();
+    let swapped = current.supplementary_pagetable.lock().list.clone();
+    for sptinfo in swapped.iter() {
+        let mut buf = vec![0u8; PG_SIZE];
+        let copied = Swap::new_page().and_then(|offset| {
+            let size = Swap::read_page(sptinfo.offset, &mut buf)?;
+            Swap::write_page(offset, &buf[..size])?;
+            Ok(offset)
+        });
+        match copied {
+            Ok(offset) => {
+                let mut copy = sptinfo.clone();
+                copy.offset = offset;
+                spt.list.push(copy);
+            }
+            Err(_) => {
+                // Out of swap, undo the copy.
+                spt.list.iter().for_each(|s| Swap::free_page(s.offset));
+                unsafe { pt.destroy() };
+                return -1;
+            }
+        }
+    }
+    drop(paused);
+
+    let mut frame = frame.clone();
+    frame.x[10] = 0; // a0
+    let userproc = UserProc::new(current.userproc.as_ref().unwrap().bin.lock().clone());
+
+    let handle = thread::Builder::new(move || start(frame))
+        .name(current.name())
         .pagetable(pt)
         .userproc(userproc)
-        .parent(thread::current())
+        .parent(current.clone())
         .set_mapping_table(mappingtable)
+        .set_supplementary_pagetable(spt)
+        .fdlist(current.fdlist.lock().clone())
         .spawn();
 
+    let child = handle.thread();
+    for (pa, va, flags) in frames {
+        GlobalFrameTable::map_for(&child, pa, va, flags);
+    }
     let childinfo = child.init_child_info();
-    thread::current().children.lock().push(childinfo);
+    current.children.lock().push(childinfo);
     child.id()
 }
 
@@ -153,12 +308,32 @@ pub fn execute(mut file: File, argv: Vec<String>) -> isize {
 ///
 /// Panic if the current thread doesn't own a user process.
 pub fn exit(value: isize) -> ! {
+    exit_as(value, false)
+}
+
+/// Exits a process killed by the kernel or by [`kill`], with [`KILLED_EXIT`].
+pub fn exit_killed() -> ! {
+    exit_as(KILLED_EXIT, true)
+}
+
+fn exit_as(value: isize, killed: bool) -> ! {
     // TODO: Lab2.
     // Well, Lab 3 also modify here.
-    let old = sbi::interrupt::set(false);
     let t = thread::current();
+    // Before the parent can see the exit, as it may read the files of shared
+    // mappings.
+    release_memory(&t);
+    // Closing files takes sleep locks, which the last owner of the thread may
+    // not, with interrupts off.
+    drop(core::mem::take(&mut t.fdlist.lock().list));
+
+    let old = sbi::interrupt::set(false);
+    let mut usage = *t.usage.lock();
+    usage += *t.children_usage.lock();
 
-    t.parent.lock().as_ref().map(|parent| {
+    // Not under our own lock, which a dropped parent takes under its own.
+    let parent = t.parent.lock().clone();
+    if let Some(parent) = parent {
         parent
             .children
             .lock()
@@ -167,6 +342,8 @@ pub fn exit(value: isize) -> ! {
             .map(|child_info| {
                 child_info.ptr = None;
                 child_info.exit_code = Some(value);
+                child_info.killed = killed;
+                child_info.usage = usage;
                 if child_info.is_waiting {
                     child_info.wait_sema.up();
                 }
@@ -178,50 +355,10 @@ pub fn exit(value: isize) -> ! {
                 child_info.is_waiting
                 || child_info.ptr.is_some()
                 || child_info.exit_code.is_some()
-        })
-    });
-
-     let pt = unsafe { PageTable::effective_pagetable() };
-     for mapinfo in t.mapping_table.lock().list.iter_mut() {
-        if mapinfo.mapid == -1 {
-            continue;
-        }
-        for i in (0..mapinfo.memsize).step_by(PG_SIZE) {
-            if let Some(entry) = pt.get_pte_mut(mapinfo.va + i) {
-                if entry.is_valid() && entry.is_dirty() {
-                    mapinfo
-                        .file
-                        .as_mut()
-                        .unwrap()
-                        .seek(SeekFrom::Start(mapinfo.offset + i))
-                        .unwrap();
-                    let size = (mapinfo.memsize - i).min(PG_SIZE);
-                    let buf = unsafe {
-                        ((mapinfo.va + i) as *const [u8; PG_SIZE]).as_ref().unwrap()
-                    };
-                    mapinfo.file.as_mut().unwrap().write(&buf[..size]);
-                }
-                unsafe {
-                    UserPool::dealloc_pages(entry.pa().into_va() as *mut _, 1);
-                }
-                entry.set_invalid();
-            }
-        }
-        pt.activate();
-    }
-    
-    for sptinfo in t
-        .supplementary_pagetable
-        .lock()
-        .list
-        .iter() {
-        Swap::push_page(sptinfo.offset);
+        });
     }
 
-    {
-        let t = thread::Manager::get().current.lock();
-        t.set_status(thread::imp::Status::Dying);
-    }
+    t.set_status(thread::imp::Status::Dying);
     
     sbi::interrupt::set(old);
     schedule();
@@ -229,41 +366,161 @@ pub fn exit(value: isize) -> ! {
     unreachable!("An exited thread shouldn't be scheduled again");
 }
 
+/// Writes back the dirty pages of shared mappings and frees them, as well as
+/// the swap slots of the process. The mappings are gone afterwards. Must run
+/// on its page table.
+fn release_memory(t: &Thread) {
+    let _paused = palloc::pause_evictions();
+    let mut mappings = core::mem::take(&mut t.mapping_table.lock().list);
+    for mapinfo in mappings.iter_mut().filter(|m| m.shared) {
+        mmap::release(mapinfo);
+    }
+
+    for sptinfo in core::mem::take(&mut t.supplementary_pagetable.lock().list) {
+        Swap::free_page(sptinfo.offset);
+    }
+}
+
+/// Moves the program break, the end of the heap of the current process, to
+/// `brk`. Returns the old break.
+pub fn brk(brk: usize) -> Result<usize> {
+    move_break(|_| Some(brk))
+}
+
+/// Moves the program break by `increment` bytes. Returns the old break.
+pub fn sbrk(increment: isize) -> Result<usize> {
+    move_break(|old| old.checked_add_signed(increment))
+}
+
+/// Moves the program break to `new(old)`. The heap can't shrink below its
+/// start, or grow into other mappings or the stack. Pages past the new break
+/// are released, along with their swap slots.
+fn move_break(new: impl FnOnce(usize) -> Option<usize>) -> Result<usize> {
+    let current = thread::current();
+    // Pages past a lowered break may still be on their way to the swap.
+    let _paused = palloc::pause_evictions();
+    let mut mapping_table = current.mapping_table.lock();
+    let heap = mapping_table.heap().ok_or(OsError::UserError)?;
+    let (start, old) = (heap.va, heap.va_end());
+
+    let new = new(old).filter(|&new| new >= start).ok_or(OsError::BadPtr)?;
+    let (old_end, new_end) = (old.ceil(), new.ceil());
+    if new_end > old_end
+        && (new_end > STACK_TOP - STACK_LIMIT
+            || !mapping_table.va_range_check(old_end, new_end))
+    {
+        return Err(OsError::OutOfMemory);
+    }
+
+    if new_end < old_end {
+        {
+            let pt = current.pagetable.as_ref().unwrap().lock();
+            for va in (new_end..old_end).step_by(PG_SIZE) {
+                if let Some(entry) = pt.get_pte_mut(va).filter(|e| e.is_valid()) {
+                    unsafe { UserPool::dealloc_pages(entry.pa().into_va() as *mut _, 1) };
+                    entry.set_invalid();
+                }
+            }
+            pt.activate();
+        }
+
+        current.supplementary_pagetable.lock().list.retain(|sptinfo| {
+            let released = (new_end..old_end).contains(&sptinfo.va);
+            if released {
+                Swap::free_page(sptinfo.offset);
+            }
+            !released
+        });
+    }
+
+    mapping_table.heap().unwrap().memsize = new - start;
+    Ok(old)
+}
+
+/// Marks the process of `tid` for termination. The process exits with
+/// [`KILLED_EXIT`] at its next safe point, i.e. when it's about to return to
+/// user mode, or wakes up in a [`Semaphore`] without holding sleep locks.
+pub fn kill(tid: isize) -> Result<()> {
+    let target = Manager::get()
+        .find_by_tid(tid)
+        .filter(|t| t.userproc.is_some())
+        .ok_or(OsError::NoSuchProcess)?;
+
+    target.kill();
+    Ok(())
+}
+
 /// Waits for a child thread, which must own a user process.
 ///
 /// ## Return
 /// - `Some(exit_value)`
 /// - `None`: if tid was not created by the current thread.
 pub fn wait(tid: isize) -> Option<isize> {
+    wait_for(tid, None).map(|exit| exit.expect("waited without a timeout").0)
+}
+
+/// Like [`wait`], but gives up after `ticks` timer ticks, unless it's `None`.
+/// Also tells whether the child was killed.
+///
+/// ## Return
+/// - `Some(Some((exit_value, killed)))`
+/// - `Some(None)`: if the child is still running after `ticks`. It can be
+/// waited for again.
+/// - `None`: if tid was not created by the current thread.
+pub fn wait_timeout(tid: isize, ticks: Option<i64>) -> Option<Option<(isize, bool)>> {
+    wait_for(tid, ticks)
+}
+
+fn wait_for(tid: isize, ticks: Option<i64>) -> Option<Option<(isize, bool)>> {
     // TODO: Lab2.
     // let old = sbi::interrupt::set(false);
-    {
-            let current = thread::current();
 
-        for childinfo in current.children.lock().iter_mut() {
-            if childinfo.tid == tid {
+    // Under one lock, so that a child can't exit between the check and the
+    // wait without waking us up.
+    let sema = {
+        let current = thread::current();
+        let mut children = current.children.lock();
+
+        match children.iter_mut().find(|child_info| child_info.tid == tid) {
+            Some(childinfo) => {
                 if let Some(ret) = childinfo.exit_code {
                     childinfo.exit_code = Some(-1);
-                    return Some(ret);
-                } 
+                    *current.children_usage.lock() += core::mem::take(&mut childinfo.usage);
+                    return Some(Some((ret, core::mem::take(&mut childinfo.killed))));
+                }
+                childinfo.is_waiting = true;
+                Some(childinfo.wait_sema.clone())
             }
+            None => None,
         }
-    }
+    };
 
-    let sema = thread::current()
-        .children
-        .lock()
-        .iter_mut()
-        .find(|child_info| child_info.tid == tid)
-        .map(|child_info| {
-            child_info.is_waiting = true;
-            child_info.wait_sema.clone()
-        });
+    if let Some(sema) = sema {
+        let timed_out = match ticks {
+            Some(ticks) => sema.down_timeout(ticks),
+            None => {
+                sema.down();
+                false
+            }
+        };
+        if timed_out {
+            let current = thread::current();
+            let mut children = current.children.lock();
+            let child_info = children.iter_mut().find(|child_info| child_info.tid == tid);
+            let exited = child_info.is_some_and(|child_info| {
+                child_info.is_waiting = false;
+                child_info.exit_code.is_some()
+            });
+            if !exited {
+                return Some(None);
+            }
+            drop(children);
 
-    // divide into 2 lines for debugging
-    sema.map(|sema| {
-        sema.down();
-    });
+            // The child exited right after we timed out, and has upped the
+            // semaphore for us. Take it, so the next wait doesn't find it.
+            sema.down();
+        }
+    }
 
     let retval = thread::current()
         .children
@@ -271,7 +528,10 @@ pub fn wait(tid: isize) -> Option<isize> {
         .iter_mut()
         .find(|child_info| child_info.tid == tid)
         .take()
-        .map(|child_info| child_info.exit_code.unwrap());
+        .map(|child_info| {
+            *thread::current().children_usage.lock() += child_info.usage;
+            child_info.exit_code.map(|ret| (ret, child_info.killed))
+        });
 
     thread::current().children.lock().retain(|child_info| child_info.tid != tid);
 
@@ -0,0 +1,117 @@
+//! Fast user-space locks.
+//!
+//! A futex is a 32-bit word in user memory. User code manipulates it with
+//! atomic instructions, and only calls into the kernel to block until the word
+//! changes ([`wait`]) or to wake up threads blocked on it ([`wake`]).
+//!
+//! Waiters are keyed by the physical address of the word, so that processes
+//! mapping the same page, such as a shared mapping inherited by a fork, meet in
+//! the same queue, wherever it's mapped. Pages with waiters are never evicted,
+//! which would change the address under them.
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::mem::palloc;
+use crate::mem::userbuf::read_user_byte;
+use crate::mem::{PhysAddr, PG_MASK, PG_SIZE};
+use crate::sync::{Lazy, Mutex, MutexGuard, Primitive, Semaphore, Spin};
+use crate::thread::scheduler::first_max;
+use crate::thread::{self, Thread};
+use crate::{OsError, Result};
+
+type Queue = VecDeque<(Arc<Thread>, Arc<Semaphore>)>;
+
+/// Waiters of each futex, keyed by physical address. Checking the value and
+/// queueing up happen under this lock, so that a wake can't slip in between.
+static QUEUES: Lazy<Mutex<BTreeMap<usize, Queue>, Spin>> = Lazy::new(Mutex::default);
+
+/// Physical address of the futex at `addr` in the current process, which stays
+/// put while the returned guard holds off evictions.
+fn physical(addr: usize) -> Result<(PhysAddr, MutexGuard<'static, (), Primitive>)> {
+    if addr % 4 != 0 {
+        return Err(OsError::BadPtr);
+    }
+
+    let current = thread::current();
+    let pagetable = current.pagetable.as_ref().ok_or(OsError::BadPtr)?;
+    loop {
+        // Faulting the page in may wait for an eviction, so it's done first.
+        read_user_byte(addr as *const u8)?;
+        let paused = palloc::pause_evictions();
+        match pagetable.lock().get_pte(addr) {
+            Some(entry) if entry.is_valid() && entry.is_user() => {
+                let pa = PhysAddr::from_pa(entry.pa().value() + (addr & PG_MASK));
+                return Ok((pa, paused));
+            }
+            // Evicted again in the meantime.
+            Some(entry) if !entry.is_valid() => continue,
+            _ => return Err(OsError::BadPtr),
+        }
+    }
+}
+
+/// Whether a thread waits on a futex in the frame at `pa`.
+pub fn is_waited(pa: usize) -> bool {
+    QUEUES.lock().range(pa..pa + PG_SIZE).next().is_some()
+}
+
+/// Blocks until woken up by [`wake`], if the futex at `addr` still holds
+/// `expected`. Fails with [`OsError::WouldBlock`] if it doesn't.
+pub fn wait(addr: usize, expected: u32) -> Result<()> {
+    let (pa, paused) = physical(addr)?;
+    let sema = Arc::new(Semaphore::new(0));
+
+    {
+        let mut queues = QUEUES.lock();
+        let value = unsafe { (pa.into_va() as *const u32).read_volatile() };
+        if value != expected {
+            return Err(OsError::WouldBlock);
+        }
+        queues
+            .entry(pa.value())
+            .or_default()
+            .push_back((thread::current(), sema.clone()));
+    }
+
+    drop(paused);
+    sema.down();
+    Ok(())
+}
+
+/// Wakes up at most `n` threads waiting on the futex at `addr`, those of the
+/// highest priority first. Returns how many were woken up.
+pub fn wake(addr: usize, n: usize) -> Result<usize> {
+    let (pa, _paused) = physical(addr)?;
+
+    let woken: Vec<_> = {
+        let mut queues = QUEUES.lock();
+        let Some(queue) = queues.get_mut(&pa.value()) else {
+            return Ok(0);
+        };
+
+        // Killed waiters are gone, or about to be.
+        queue.retain(|(t, _)| !t.is_killed());
+        let mut woken = Vec::new();
+        while woken.len() < n {
+            let highest = first_max(
+                queue
+                    .iter()
+                    .map(|(t, _)| t.effective_priority())
+                    .enumerate(),
+            );
+            match highest.and_then(|i| queue.remove(i)) {
+                Some((_, sema)) => woken.push(sema),
+                None => break,
+            }
+        }
+        if queue.is_empty() {
+            queues.remove(&pa.value());
+        }
+        woken
+    };
+
+    woken.iter().for_each(|sema| sema.up());
+    Ok(woken.len())
+}
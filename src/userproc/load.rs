@@ -34,7 +34,7 @@ pub(super) fn load_executable(
     let (exec_info, mappingtable) = load_elf(file)?;
 
     // Initialize user stack.
-    init_user_stack(pagetable, exec_info.init_sp);
+    init_user_stack(pagetable, exec_info.init_sp)?;
 
     // Forbid modifying executable file when running
     file.deny_write();
@@ -63,6 +63,15 @@ fn load_elf(file: &mut File) -> Result<(ExecInfo, MappingTable)> {
         .filter(|p| p.ph_type() == ProgramType::LOAD)
         .for_each(|p| load_segment(file, &p, &mut mappingtable));
 
+    // The heap starts empty, right after the highest segment.
+    let heap = mappingtable
+        .list
+        .iter()
+        .map(|m| m.va_end().ceil())
+        .max()
+        .unwrap_or(0);
+    mappingtable.list.push(MapInfo::heap(heap));
+
     Ok((ExecInfo {
             entry_point: elf.elf_header().entry_point() as _,
             init_sp: 0x80500000,
@@ -136,11 +145,14 @@ fn load_segment(file: &mut File, phdr: &ProgramHeaderEntry, mappingtable: &mut M
 }
 
 /// Initializes the user stack.
-fn init_user_stack(pagetable: &mut PageTable, init_sp: usize) {
+fn init_user_stack(pagetable: &mut PageTable, init_sp: usize) -> Result<()> {
     assert!(init_sp % PG_SIZE == 0, "initial sp address misaligns");
 
     // Allocate a page from UserPool as user stack.
     let stack_va = unsafe { UserPool::alloc_pages(1) };
+    if stack_va.is_null() {
+        return Err(OsError::OutOfMemory);
+    }
     let stack_pa = PhysAddr::from(stack_va);
 
     // Get the start address of stack page
@@ -156,4 +168,6 @@ fn init_user_stack(pagetable: &mut PageTable, init_sp: usize) {
         stack_va,
         stack_page_begin
     );
+
+    Ok(())
 }
@@ -0,0 +1,214 @@
+//! Memory mappings made by user programs.
+//!
+//! A mapping is backed by a file, whose pages are read in when first touched,
+//! or anonymous, with pages that start out as zeros. Changes to a shared
+//! mapping are written back to its file, while a private mapping keeps them to
+//! itself, in memory or in the swap.
+//!
+//! Each process reads the pages of a shared mapping into frames of its own, so
+//! processes mapping the same file only see each other's changes once they're
+//! written back, when a page is evicted or unmapped. Only a forked child shares
+//! the frames its parent had read in.
+
+use alloc::vec::Vec;
+
+use crate::fs::disk::Swap;
+use crate::fs::File;
+use crate::io::{Seek, SeekFrom, Write};
+use crate::mem::mappingtable::{MapInfo, MappingTable};
+use crate::mem::palloc::{self, UserPool};
+use crate::mem::{PTEFlags, PageAlign, PG_SIZE};
+use crate::thread::{self, STACK_TOP};
+use crate::trap::STACK_LIMIT;
+use crate::{OsError, Result};
+
+pub const PROT_READ: usize = 0x1;
+pub const PROT_WRITE: usize = 0x2;
+pub const PROT_EXEC: usize = 0x4;
+
+pub const MAP_SHARED: usize = 0x01;
+pub const MAP_PRIVATE: usize = 0x02;
+pub const MAP_FIXED: usize = 0x10;
+pub const MAP_ANONYMOUS: usize = 0x20;
+
+/// Mappings stay below the area reserved for the stack.
+const MMAP_TOP: usize = STACK_TOP - STACK_LIMIT;
+
+/// Maps `len` bytes of `file` from `offset`, or anonymous memory if `file` is
+/// `None`, with the access rights of `prot`. Returns the address of the new
+/// mapping.
+///
+/// With [`MAP_FIXED`], the mapping is placed at `addr`, which must be free.
+/// Otherwise `addr` is only a hint, and the kernel picks the highest free range
+/// below the stack when it's taken, or is 0.
+pub fn mmap(
+    addr: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    file: Option<File>,
+    offset: usize,
+) -> Result<usize> {
+    let shared = match flags & (MAP_SHARED | MAP_PRIVATE) {
+        MAP_SHARED => true,
+        MAP_PRIVATE => false,
+        _ => return Err(OsError::UserError),
+    };
+    // A leaf page without any rights can't be expressed on RISC-V. Anonymous
+    // pages live in a single process, so they can't be shared.
+    if prot == 0
+        || prot & !(PROT_READ | PROT_WRITE | PROT_EXEC) != 0
+        || flags & !(MAP_SHARED | MAP_PRIVATE | MAP_FIXED | MAP_ANONYMOUS) != 0
+        || (flags & MAP_ANONYMOUS != 0) != file.is_none()
+        || (shared && file.is_none())
+        || offset % PG_SIZE != 0
+    {
+        return Err(OsError::UserError);
+    }
+    if len == 0 || len > MMAP_TOP {
+        return Err(OsError::OutOfMemory);
+    }
+
+    // Pages can't be writable without being readable either.
+    let mut pte_flags = PTEFlags::U;
+    if prot & (PROT_READ | PROT_WRITE) != 0 {
+        pte_flags |= PTEFlags::R;
+    }
+    if prot & PROT_WRITE != 0 {
+        pte_flags |= PTEFlags::W;
+    }
+    if prot & PROT_EXEC != 0 {
+        pte_flags |= PTEFlags::X;
+    }
+
+    let filesize = match file.as_ref() {
+        Some(file) => file.len()?.saturating_sub(offset).min(len),
+        None => 0,
+    };
+
+    let current = thread::current();
+    let mut mapping_table = current.mapping_table.lock();
+    let size = len.ceil();
+    let free = |mapping_table: &MappingTable, va: usize| {
+        va != 0
+            && va % PG_SIZE == 0
+            && va <= MMAP_TOP - size
+            && !mapping_table.list.iter().any(|m| m.overlaps(va, va + size))
+    };
+    let va = if free(&mapping_table, addr) {
+        addr
+    } else if flags & MAP_FIXED != 0 {
+        return Err(OsError::UserError);
+    } else {
+        find_free(&mapping_table, size).ok_or(OsError::OutOfMemory)?
+    };
+
+    let mut mapinfo = MapInfo::new(0, file, offset, va, filesize, len, pte_flags);
+    mapinfo.shared = shared;
+    mapping_table.map(mapinfo);
+    Ok(va)
+}
+
+/// The highest free range of `size` bytes between the heap and the stack.
+fn find_free(mapping_table: &MappingTable, size: usize) -> Option<usize> {
+    let bottom = mapping_table
+        .list
+        .iter()
+        .find(|m| m.mapid == -1 && m.file.is_none())
+        .map_or(PG_SIZE, |heap| heap.va_end().ceil());
+
+    let mut end = MMAP_TOP;
+    loop {
+        let va = end.checked_sub(size).filter(|&va| va >= bottom)?;
+        match mapping_table
+            .list
+            .iter()
+            .filter(|m| m.overlaps(va, end))
+            .map(|m| m.va)
+            .min()
+        {
+            Some(below) => end = below.floor(),
+            None => return Some(va),
+        }
+    }
+}
+
+/// Removes the mappings in `[addr, addr + len)`. Mappings aren't split, so
+/// one that is only partly in the range can't be removed, and neither can the
+/// heap or the executable.
+pub fn munmap(addr: usize, len: usize) -> Result<()> {
+    let end = addr.checked_add(len).ok_or(OsError::BadPtr)?;
+    if addr % PG_SIZE != 0 || len == 0 || end > MMAP_TOP {
+        return Err(OsError::UserError);
+    }
+
+    let mut mapids = Vec::new();
+    for mapinfo in thread::current()
+        .mapping_table
+        .lock()
+        .list
+        .iter()
+        .filter(|m| m.overlaps(addr, end.ceil()))
+    {
+        if mapinfo.mapid == -1 || mapinfo.va < addr || mapinfo.va_end().ceil() > end.ceil() {
+            return Err(OsError::UserError);
+        }
+        mapids.push(mapinfo.mapid);
+    }
+    mapids.into_iter().for_each(unmap);
+    Ok(())
+}
+
+/// Removes the mapping `mapid` of the current process, if it exists, and frees
+/// its pages and swap slots.
+pub fn unmap(mapid: isize) {
+    let current = thread::current();
+    let _paused = palloc::pause_evictions();
+    let mut mapinfo = {
+        let mut mapping_table = current.mapping_table.lock();
+        let Some(pos) = mapping_table.list.iter().position(|m| m.mapid == mapid) else {
+            return;
+        };
+        mapping_table.list.remove(pos)
+    };
+    release(&mut mapinfo);
+
+    let range = mapinfo.va..mapinfo.va_end().ceil();
+    current.supplementary_pagetable.lock().list.retain(|sptinfo| {
+        let released = range.contains(&sptinfo.va);
+        if released {
+            Swap::free_page(sptinfo.offset);
+        }
+        !released
+    });
+}
+
+/// Frees the pages of `mapinfo` that are in memory, taken out of the mapping
+/// table of the current process already. Dirty pages of a shared mapping are
+/// written back to its file first, so evictions must be paused.
+pub fn release(mapinfo: &mut MapInfo) {
+    let current = thread::current();
+    let pagetable = current.pagetable.as_ref().expect("not a user process");
+    for i in (0..mapinfo.memsize).step_by(PG_SIZE) {
+        let va = mapinfo.va + i;
+        let dirty = match pagetable.lock().get_pte(va) {
+            Some(entry) if entry.is_valid() => entry.is_dirty(),
+            _ => continue,
+        };
+        // Not under the lock, writing to a file may block.
+        if mapinfo.shared && dirty {
+            let file = mapinfo.file.as_mut().unwrap();
+            file.seek(SeekFrom::Start(mapinfo.offset + i)).unwrap();
+            let size = (mapinfo.filesize.max(i) - i).min(PG_SIZE);
+            let buf = unsafe { (va as *const [u8; PG_SIZE]).as_ref().unwrap() };
+            let _ = file.write(&buf[..size]);
+        }
+        let pt = pagetable.lock();
+        let entry = pt.get_pte_mut(va).unwrap();
+        unsafe {
+            UserPool::dealloc_pages(entry.pa().into_va() as *mut _, 1);
+        }
+        entry.set_invalid();
+    }
+    pagetable.lock().activate();
+}
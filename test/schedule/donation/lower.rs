@@ -42,7 +42,7 @@ pub fn main() {
     lock.release();
 
     assert_eq!(
-        child.status(),
+        child.thread().status(),
         Status::Dying,
         "Child thread must have finished."
     );
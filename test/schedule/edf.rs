@@ -0,0 +1,14 @@
+pub mod admit;
+pub mod budget;
+pub mod order;
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicI64, Ordering::SeqCst};
+
+use crate::sbi::timer::{self, TICKS_PER_SEC};
+use crate::sync::{Mutex, Semaphore};
+use crate::thread::*;
+use crate::OsError;
+
+use super::{pass, spinner};
@@ -0,0 +1,44 @@
+//!
+//! Fills the CPU up with real-time threads, verifies that one more is rejected,
+//! and that it is admitted once a reservation is given back.
+//!
+
+use super::*;
+
+const PERIOD: i64 = 8;
+
+/// Spawns a real-time thread that waits on `go` before exiting.
+fn reserve(runtime: i64, go: &Arc<Semaphore>) -> crate::Result<JoinHandle<()>> {
+    let go = go.clone();
+    Builder::new(move || go.down())
+        .name("realtime")
+        .realtime(PERIOD, runtime, PERIOD)
+        .try_spawn()
+}
+
+pub fn main() {
+    let go = Arc::new(Semaphore::new(0));
+
+    // 50% + 37.5% of the CPU
+    let half = reserve(4, &go).expect("50% should be admitted");
+    let rest = reserve(3, &go).expect("37.5% should be admitted");
+
+    // 25% more would overload the CPU, while 12.5% fills it up exactly.
+    assert!(matches!(reserve(2, &go), Err(OsError::Overloaded)));
+    let full = reserve(1, &go).expect("12.5% should be admitted");
+    assert!(matches!(reserve(1, &go), Err(OsError::Overloaded)));
+
+    // Once the first thread exits, its share is available again.
+    go.up();
+    half.join();
+    let again = reserve(2, &go).expect("25% should be admitted");
+
+    for _ in 0..3 {
+        go.up();
+    }
+    rest.join();
+    full.join();
+    again.join();
+
+    pass();
+}
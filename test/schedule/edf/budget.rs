@@ -0,0 +1,53 @@
+//!
+//! Runs a CPU-bound real-time thread reserving 3 ticks in every 10 next to a
+//! normal one, and verifies that it is throttled down to 30% of the CPU.
+//!
+
+use super::*;
+
+const SECS: i64 = 10;
+const PERIOD: i64 = 10;
+const RUNTIME: i64 = 3;
+
+pub fn main() {
+    let end = timer::timer_ticks() + SECS * TICKS_PER_SEC as i64;
+    let done = Arc::new(Semaphore::new(0));
+    let ticks: [Arc<AtomicI64>; 2] = core::array::from_fn(|_| Arc::new(AtomicI64::new(0)));
+
+    // The normal spinner must not run before the real-time one is created.
+    set_priority(PRI_MAX);
+    {
+        let (ticks, done) = (ticks[0].clone(), done.clone());
+        Builder::new(move || spinner(end, ticks, done))
+            .name("normal")
+            .spawn();
+    }
+    {
+        let (ticks, done) = (ticks[1].clone(), done.clone());
+        Builder::new(move || spinner(end, ticks, done))
+            .name("realtime")
+            .realtime(PERIOD, RUNTIME, PERIOD)
+            .spawn();
+    }
+
+    // Stay blocked, so that the spinners split the CPU among themselves.
+    for _ in 0..2 {
+        done.down();
+    }
+
+    let [normal, realtime] = ticks.map(|t| t.load(SeqCst));
+    let total = normal + realtime;
+    let expected = total * RUNTIME / PERIOD;
+    kprintln!(
+        "Real-time thread got {} of {} ticks, expected {}.",
+        realtime,
+        total,
+        expected
+    );
+    assert!(
+        (realtime - expected).abs() <= total / 20,
+        "Real-time thread is not held to its budget."
+    );
+
+    pass();
+}
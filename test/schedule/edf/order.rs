@@ -0,0 +1,37 @@
+//!
+//! Releases three real-time threads at once, and verifies that they run in
+//! the order of their deadlines rather than their creation.
+//!
+
+use super::*;
+
+const PERIOD: i64 = 100;
+const DEADLINES: [i64; 3] = [30, 10, 20];
+
+pub fn main() {
+    let order: Arc<Mutex<Vec<usize>>> = Arc::new(Mutex::new(Vec::new()));
+    let order2 = order.clone();
+
+    // The launcher has the earliest deadline, so none of the threads it
+    // creates runs before all of them exist.
+    Builder::new(move || {
+        for (i, deadline) in DEADLINES.iter().enumerate() {
+            let order = order2.clone();
+            Builder::new(move || order.lock().push(i))
+                .name("realtime")
+                .realtime(PERIOD, 1, *deadline)
+                .spawn();
+        }
+    })
+    .name("launcher")
+    .realtime(PERIOD, 2, 5)
+    .spawn()
+    .join();
+
+    // Real-time threads run before us, so they are done by now.
+    let order = order.lock().clone();
+    kprintln!("Real-time threads ran in order {:?}.", order);
+    assert_eq!(order, [1, 2, 0], "not in the order of deadlines");
+
+    pass();
+}
@@ -0,0 +1,37 @@
+pub mod fair;
+pub mod load;
+pub mod nice;
+
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicI64, Ordering::SeqCst};
+
+use crate::sbi::timer::{self, TICKS_PER_SEC};
+use crate::sync::Semaphore;
+use crate::thread::*;
+
+use super::{pass, spinner};
+
+/// Runs a spinner for each nice value in `nices` during `secs` seconds, and
+/// returns how many ticks each of them got.
+fn spin_with_nices<const N: usize>(nices: [i32; N], secs: i64) -> [i64; N] {
+    let end = timer::timer_ticks() + secs * TICKS_PER_SEC as i64;
+    let done = Arc::new(Semaphore::new(0));
+    let ticks: [Arc<AtomicI64>; N] = core::array::from_fn(|_| Arc::new(AtomicI64::new(0)));
+
+    // Spinners inherit our nice value.
+    for (nice, ticks) in nices.iter().zip(ticks.iter()) {
+        set_nice(*nice);
+        let (ticks, done) = (ticks.clone(), done.clone());
+        Builder::new(move || spinner(end, ticks, done))
+            .name("spinner")
+            .spawn();
+    }
+    set_nice(NICE_DEFAULT);
+
+    // Stay blocked, so that the spinners split the CPU among themselves.
+    for _ in 0..N {
+        done.down();
+    }
+
+    core::array::from_fn(|i| ticks[i].load(SeqCst))
+}
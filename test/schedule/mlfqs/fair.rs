@@ -0,0 +1,26 @@
+//!
+//! Starts two CPU-bound threads with the same nice value, and verifies that
+//! they get about the same share of the CPU.
+//!
+
+use super::*;
+
+const SECS: i64 = 20;
+
+pub fn main() {
+    let ticks = spin_with_nices([0, 0], SECS);
+    kprintln!("Thread 0 got {} ticks, thread 1 got {} ticks.", ticks[0], ticks[1]);
+
+    let total = ticks[0] + ticks[1];
+    for (i, t) in ticks.iter().enumerate() {
+        assert!(
+            (total * 2 / 5..=total * 3 / 5).contains(t),
+            "Thread {} got {} of {} ticks, which is unfair.",
+            i,
+            t,
+            total
+        );
+    }
+
+    pass();
+}
@@ -0,0 +1,41 @@
+//!
+//! Verifies that a single busy thread raises the load average to 0.5 in 38
+//! to 45 seconds. The expected time is 42 seconds, as you can verify:
+//! `perl -e '$i++,$a=(59*$a+1)/60while$a<=.5;print "$i\n"'`
+//!
+
+use super::*;
+
+pub fn main() {
+    let start = timer::timer_ticks();
+    kprintln!("Spinning for up to 45 seconds, please wait...");
+
+    let elapsed = loop {
+        let load_avg = get_load_avg();
+        let elapsed = timer::timer_elapsed(start) / TICKS_PER_SEC as i64;
+
+        assert!(
+            (0..=100).contains(&load_avg),
+            "Load average is {}.{:02} but should be between 0 and 1 (after {} seconds).",
+            load_avg / 100,
+            load_avg % 100,
+            elapsed,
+        );
+        if load_avg > 50 {
+            break elapsed;
+        }
+        assert!(
+            elapsed <= 45,
+            "Load average stayed below 0.5 for more than 45 seconds."
+        );
+    };
+
+    assert!(
+        elapsed >= 38,
+        "Load average rose to 0.5 after only {} seconds.",
+        elapsed
+    );
+    kprintln!("Load average rose to 0.5 after {} seconds.", elapsed);
+
+    pass();
+}
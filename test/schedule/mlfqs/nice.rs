@@ -0,0 +1,20 @@
+//!
+//! Starts two CPU-bound threads with nice values 0 and 5, and verifies that
+//! the nicer one gets less of the CPU.
+//!
+
+use super::*;
+
+const SECS: i64 = 20;
+
+pub fn main() {
+    let ticks = spin_with_nices([0, 5], SECS);
+    kprintln!("Nice 0 got {} ticks, nice 5 got {} ticks.", ticks[0], ticks[1]);
+
+    assert!(
+        ticks[0] > ticks[1],
+        "The thread with nice 5 should have gotten fewer ticks."
+    );
+
+    pass();
+}
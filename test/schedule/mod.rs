@@ -2,13 +2,37 @@
 
 mod alarm;
 mod donation;
+mod edf;
+mod mlfqs;
 mod priority;
+mod stride;
+
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicI64, Ordering::SeqCst};
+
+use crate::sbi::timer;
+use crate::sync::Semaphore;
 
 fn pass() {
     kprintln!("[PASS]");
 }
 
-static NAME2CASE: [(&str, fn()); 18] = [
+/// Spins until tick `end`, counting the ticks at which it held the CPU.
+///
+/// Takes no lock while spinning, so timer interrupts can preempt it.
+fn spinner(end: i64, ticks: Arc<AtomicI64>, done: Arc<Semaphore>) {
+    let mut last = timer::timer_ticks();
+    while last < end {
+        let now = timer::timer_ticks();
+        if now != last {
+            ticks.fetch_add(1, SeqCst);
+            last = now;
+        }
+    }
+    done.up();
+}
+
+static NAME2CASE: [(&str, fn()); 25] = [
     ("alarm-zero", alarm::boundary::zero::main),
     ("alarm-negative", alarm::boundary::negative::main),
     ("alarm-simultaneous", alarm::simultaneous::main),
@@ -27,6 +51,13 @@ static NAME2CASE: [(&str, fn()); 18] = [
     ("donation-sema", donation::sema::main),
     ("donation-two", donation::two::main),
     ("donation-three", donation::three::main),
+    ("mlfqs-load-1", mlfqs::load::main),
+    ("mlfqs-fair-2", mlfqs::fair::main),
+    ("mlfqs-nice-2", mlfqs::nice::main),
+    ("stride-split", stride::split::main),
+    ("edf-admit", edf::admit::main),
+    ("edf-order", edf::order::main),
+    ("edf-budget", edf::budget::main),
 ];
 
 pub fn main(case: &str) {
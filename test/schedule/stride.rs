@@ -0,0 +1,10 @@
+pub mod split;
+
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicI64, Ordering::SeqCst};
+
+use crate::sbi::timer::{self, TICKS_PER_SEC};
+use crate::sync::Semaphore;
+use crate::thread::*;
+
+use super::{pass, spinner};
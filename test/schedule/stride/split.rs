@@ -0,0 +1,52 @@
+//!
+//! Starts three CPU-bound threads holding 10, 20 and 30 tickets, and verifies
+//! that they split the CPU in a 1:2:3 ratio.
+//!
+
+use super::*;
+
+const SECS: i64 = 20;
+const TICKETS: [u32; 3] = [10, 20, 30];
+
+pub fn main() {
+    let end = timer::timer_ticks() + SECS * TICKS_PER_SEC as i64;
+    let done = Arc::new(Semaphore::new(0));
+    let ticks: [Arc<AtomicI64>; 3] = core::array::from_fn(|_| Arc::new(AtomicI64::new(0)));
+
+    // Spinners are preempted by the timer, but must not run before all of them
+    // have been created.
+    set_priority(PRI_MAX);
+    for (tickets, ticks) in TICKETS.iter().zip(ticks.iter()) {
+        let (ticks, done) = (ticks.clone(), done.clone());
+        Builder::new(move || spinner(end, ticks, done))
+            .name("spinner")
+            .priority(*tickets)
+            .spawn();
+    }
+
+    // Stay blocked, so that the spinners split the CPU among themselves.
+    for _ in TICKETS {
+        done.down();
+    }
+
+    let ticks = ticks.map(|t| t.load(SeqCst));
+    let total: i64 = ticks.iter().sum();
+    let all_tickets: u32 = TICKETS.iter().sum();
+
+    for i in 0..TICKETS.len() {
+        let expected = total * TICKETS[i] as i64 / all_tickets as i64;
+        kprintln!(
+            "Thread with {} tickets got {} of {} ticks, expected {}.",
+            TICKETS[i],
+            ticks[i],
+            total,
+            expected
+        );
+        assert!(
+            (ticks[i] - expected).abs() <= total / 20,
+            "CPU time is not split in proportion to tickets."
+        );
+    }
+
+    pass();
+}
@@ -1,19 +1,35 @@
 mod fs;
 mod malloc;
+mod pagecache;
 mod sync;
 mod thread;
 mod virtio;
 
 pub fn main() {
+    #[cfg(any(feature = "test-sync", feature = "test-sync-barrier"))]
+    sync::barrier::main();
+    #[cfg(any(feature = "test-sync", feature = "test-sync-channel"))]
+    sync::channel::main();
     #[cfg(any(feature = "test-sync", feature = "test-sync-condvar"))]
     sync::condvar::main();
     #[cfg(any(feature = "test-sync", feature = "test-sync-sema_fifo"))]
     sync::sema_fifo::main();
+    #[cfg(any(feature = "test-sync", feature = "test-sync-rwlock"))]
+    sync::rwlock::main();
+    #[cfg(any(feature = "test-sync", feature = "test-sync-timeout"))]
+    sync::timeout::main();
+    // ! This should fail.
+    #[cfg(feature = "test-sync-lockdep")]
+    sync::lockdep::main();
 
     #[cfg(any(feature = "test-thread", feature = "test-thread-adder"))]
     thread::adder::main();
     #[cfg(any(feature = "test-thread", feature = "test-thread-block"))]
     thread::block::main();
+    #[cfg(any(feature = "test-thread", feature = "test-thread-join"))]
+    thread::join::main();
+    #[cfg(any(feature = "test-thread", feature = "test-thread-stack"))]
+    thread::stack::main();
 
     // ! This should fail.
     #[cfg(any(feature = "test-thread", feature = "test-thread-bomb"))]
@@ -35,6 +51,9 @@ pub fn main() {
     #[cfg(feature = "test-mem-malloc")]
     malloc::main();
 
+    #[cfg(feature = "test-mem-pagecache")]
+    pagecache::main();
+
     #[cfg(feature = "test-fs-inmem")]
     fs::inmem::main();
 
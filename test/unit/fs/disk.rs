@@ -1,6 +1,8 @@
+mod cache;
 mod chlen;
 mod readimg;
 mod simple;
+mod swap;
 mod sync;
 
 pub fn main() {
@@ -9,7 +11,15 @@ pub fn main() {
         simple::main();
         readimg::main().unwrap();
     }
-    #[cfg(not(feature = "test-fs-disk-simple"))]
+    #[cfg(feature = "test-fs-disk-cache")]
+    cache::main();
+    #[cfg(feature = "test-fs-disk-swap")]
+    swap::main();
+    #[cfg(not(any(
+        feature = "test-fs-disk-simple",
+        feature = "test-fs-disk-cache",
+        feature = "test-fs-disk-swap"
+    )))]
     {
         // chlen::main().unwrap();
         sync::main();
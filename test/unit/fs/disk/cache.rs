@@ -0,0 +1,48 @@
+use crate::device::virtio::SECTOR_SIZE;
+use crate::fs::disk::{BufferCache, DISKFS};
+use crate::fs::{File, FileSys};
+use crate::io::prelude::*;
+
+const FNAME: &str = "/disk-cache";
+
+/// More sectors than the cache holds, so that some are evicted.
+const SECTORS: usize = 160;
+
+fn check(file: &mut File) {
+    let mut buf = [0u8; SECTOR_SIZE];
+    file.rewind().unwrap();
+    for i in 0..SECTORS {
+        file.read_exact(&mut buf).unwrap();
+        assert!(buf.iter().all(|&b| b == i as u8), "sector {} is corrupted", i);
+    }
+}
+
+pub fn main() {
+    let mut file = DISKFS.create(FNAME.into()).unwrap();
+    for i in 0..SECTORS {
+        file.write_all(&[i as u8; SECTOR_SIZE]).unwrap();
+    }
+
+    // The last ones are still dirty in the cache, the others were written
+    // back when evicted.
+    check(&mut file);
+    kprintln!("[DISKFS.CACHE] Read back through the cache.");
+
+    BufferCache::flush();
+    check(&mut file);
+    kprintln!("[DISKFS.CACHE] Read back after flushing.");
+
+    // Overwrite a few bytes in the middle of a sector.
+    file.seek(SeekFrom::Start(3 * SECTOR_SIZE + 100)).unwrap();
+    file.write_all(&[0xff; 8]).unwrap();
+    let mut buf = [0u8; SECTOR_SIZE];
+    file.seek(SeekFrom::Start(3 * SECTOR_SIZE)).unwrap();
+    file.read_exact(&mut buf).unwrap();
+    assert!(buf[..100].iter().all(|&b| b == 3));
+    assert!(buf[100..108].iter().all(|&b| b == 0xff));
+    assert!(buf[108..].iter().all(|&b| b == 3));
+
+    drop(file);
+    DISKFS.remove(FNAME.into()).unwrap();
+    kprintln!("[DISKFS.CACHE] Done.");
+}
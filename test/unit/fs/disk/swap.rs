@@ -0,0 +1,43 @@
+use alloc::vec::Vec;
+
+use crate::fs::disk::Swap;
+use crate::mem::PG_SIZE;
+use crate::OsError;
+
+pub fn main() {
+    let before = Swap::stats();
+    assert_eq!(before.slots, Swap::page_num());
+
+    // Take every free slot.
+    let mut taken = Vec::new();
+    while let Ok(pos) = Swap::new_page() {
+        assert_eq!(pos % PG_SIZE, 0);
+        taken.push(pos);
+    }
+    assert_eq!(taken.len(), before.slots - before.used);
+    assert_eq!(Swap::new_page(), Err(OsError::SwapFull));
+    kprintln!("[DISKFS.SWAP] Got all {} free slots.", taken.len());
+
+    let full = Swap::stats();
+    assert_eq!(full.used, full.slots);
+    assert_eq!(full.peak, full.slots);
+    assert_eq!(full.failures, before.failures + 2);
+
+    // Slots are reusable, and access out of them fails.
+    let pos = taken.pop().unwrap();
+    Swap::free_page(pos);
+    assert_eq!(Swap::new_page(), Ok(pos));
+    assert_eq!(
+        Swap::write_page(Swap::len(), &[0; 8]),
+        Err(OsError::InvalidSwapSlot)
+    );
+    assert_eq!(
+        Swap::write_page(PG_SIZE - 4, &[0; 8]),
+        Err(OsError::InvalidSwapSlot)
+    );
+
+    taken.push(pos);
+    taken.into_iter().for_each(Swap::free_page);
+    assert_eq!(Swap::stats().used, before.used);
+    kprintln!("[DISKFS.SWAP] Done.");
+}
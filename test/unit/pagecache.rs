@@ -0,0 +1,67 @@
+use crate::mem::pagecache::PageCache;
+use crate::mem::palloc::UserPool;
+use crate::mem::{PhysAddr, PG_SIZE};
+
+/// No inode has this number, so the pages are only ever ours.
+const INUM: usize = usize::MAX;
+
+fn read_page(fill: u8) -> PhysAddr {
+    let page = unsafe { UserPool::alloc_pages(1) };
+    assert!(!page.is_null(), "user pool exhausted");
+    unsafe { page.write_bytes(fill, PG_SIZE) };
+    PhysAddr::from(page)
+}
+
+fn free_page(pa: PhysAddr) {
+    unsafe { UserPool::dealloc_pages(pa.into_va() as *mut _, 1) };
+}
+
+pub fn main() {
+    let before = PageCache::stats();
+
+    // The first process reads the page from disk.
+    assert!(PageCache::get(INUM, 0, PG_SIZE).is_none());
+    let first = read_page(1);
+    assert_eq!(PageCache::insert(INUM, 0, PG_SIZE, first), first);
+
+    // The next ones map the same frame, without reading it again.
+    let second = PageCache::get(INUM, 0, PG_SIZE).unwrap();
+    let third = PageCache::get(INUM, 0, PG_SIZE).unwrap();
+    assert!(second == first && third == first);
+    assert_eq!(unsafe { *(first.into_va() as *const u8) }, 1);
+
+    // One that read it at the same time gets the cached frame instead.
+    let racing = read_page(2);
+    assert_eq!(PageCache::insert(INUM, 0, PG_SIZE, racing), first);
+
+    // A page of another length is a different one.
+    assert!(PageCache::get(INUM, 0, 100).is_none());
+
+    let stats = PageCache::stats();
+    assert_eq!(stats.pages, before.pages + 1);
+    assert_eq!(stats.hits, before.hits + 3);
+    assert_eq!(stats.reads, before.reads + 2);
+    kprintln!(
+        "[PAGECACHE] 4 mappings, {} disk reads, 1 frame.",
+        stats.reads - before.reads
+    );
+
+    // Pages in use stay, those only the cache refers to are freed.
+    free_page(first);
+    free_page(second);
+    free_page(third);
+    PageCache::shrink();
+    assert_eq!(PageCache::stats().pages, before.pages + 1);
+    free_page(first);
+    assert!(PageCache::shrink());
+    assert_eq!(PageCache::stats().pages, before.pages);
+
+    // A file being written drops its pages.
+    let page = read_page(3);
+    PageCache::insert(INUM, PG_SIZE, PG_SIZE, page);
+    PageCache::invalidate(INUM);
+    assert!(PageCache::get(INUM, PG_SIZE, PG_SIZE).is_none());
+    free_page(page);
+
+    kprintln!("[PAGECACHE] Done.");
+}
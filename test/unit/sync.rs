@@ -1,2 +1,19 @@
+pub mod barrier;
+pub mod channel;
 pub mod condvar;
+#[cfg(feature = "lockdep")]
+pub mod lockdep;
+pub mod rwlock;
 pub mod sema_fifo;
+pub mod timeout;
+
+use crate::thread::{Builder, JoinHandle};
+
+/// Spawns a thread named `name` that runs `f`. Given a higher priority than
+/// us, it runs right away, until it blocks on what the test is about.
+fn spawn<F>(name: &'static str, priority: u32, f: F) -> JoinHandle<()>
+where
+    F: FnOnce() + Send + 'static,
+{
+    Builder::new(f).name(name).priority(priority).spawn()
+}
@@ -0,0 +1,42 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+
+use crate::sync::Barrier;
+use crate::thread;
+
+const THREADS: usize = 4;
+const ROUNDS: usize = 3;
+
+pub fn main() {
+    let barrier = Arc::new(Barrier::new(THREADS));
+    let arrived = Arc::new(AtomicUsize::new(0));
+    let leaders = Arc::new(AtomicUsize::new(0));
+
+    let round = {
+        let (barrier, arrived, leaders) = (barrier.clone(), arrived.clone(), leaders.clone());
+        move || {
+            for i in 0..ROUNDS {
+                arrived.fetch_add(1, SeqCst);
+                thread::schedule();
+                if barrier.wait() {
+                    leaders.fetch_add(1, SeqCst);
+                }
+                // Nobody passes before everyone of this round has arrived.
+                assert!(arrived.load(SeqCst) >= (i + 1) * THREADS);
+            }
+        }
+    };
+
+    let handles: Vec<_> = (1..THREADS)
+        .map(|_| thread::spawn("barrier", round.clone()))
+        .collect();
+    round();
+    for handle in handles {
+        handle.join();
+    }
+
+    assert_eq!(arrived.load(SeqCst), ROUNDS * THREADS);
+    assert_eq!(leaders.load(SeqCst), ROUNDS);
+    kprintln!("Barrier test done.");
+}
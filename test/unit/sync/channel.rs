@@ -0,0 +1,44 @@
+use alloc::vec::Vec;
+
+use crate::sync::{channel, Sender};
+use crate::thread::{JoinHandle, PRI_DEFAULT};
+
+use super::spawn;
+
+/// Spawns a thread that sends its `name` through `tx`.
+fn sender(name: &'static str, priority: u32, tx: &Sender<&'static str>) -> JoinHandle<()> {
+    let tx = tx.clone();
+    spawn(name, priority, move || tx.send(name).unwrap())
+}
+
+pub fn main() {
+    let (tx, rx) = channel(2);
+
+    assert_eq!(rx.try_recv(), None);
+    tx.send("first").unwrap();
+    tx.try_send("second").unwrap();
+    assert_eq!(tx.try_send("full"), Err("full"));
+
+    // Blocked senders are let in by priority.
+    let handles = [
+        sender("low", PRI_DEFAULT + 1, &tx),
+        sender("high", PRI_DEFAULT + 2, &tx),
+    ];
+    drop(tx);
+
+    let mut received = Vec::new();
+    while let Some(value) = rx.recv() {
+        received.push(value);
+    }
+    assert_eq!(received, ["first", "second", "high", "low"]);
+    for handle in handles {
+        handle.join();
+    }
+
+    // Sending fails once the receiver is gone.
+    let (tx, rx) = channel(1);
+    drop(rx);
+    assert_eq!(tx.send(1), Err(1));
+
+    kprintln!("Channel test done.");
+}
@@ -0,0 +1,44 @@
+//! Locks taken in a consistent order pass the validator. Then, taking two of
+//! them in the reverse order panics, though it doesn't deadlock by itself.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::sync::Mutex;
+use crate::thread;
+
+pub fn main() {
+    let pair: Arc<(Mutex<i32>, Mutex<i32>)> = Arc::new((Mutex::new(0), Mutex::new(0)));
+
+    // The same order on different threads
+    for _ in 0..2 {
+        let pair = pair.clone();
+        thread::spawn("ordered", move || {
+            let (a, b) = &*pair;
+            let mut a = a.lock();
+            let mut b = b.lock();
+            *a += 1;
+            *b += 1;
+        })
+        .join();
+    }
+
+    // Locks of the same class nest in any order.
+    let locks: Vec<Mutex<i32>> = (0..2).map(|_| Mutex::new(0)).collect();
+    {
+        let _first = locks[0].lock();
+        let _second = locks[1].lock();
+    }
+    {
+        let _second = locks[1].lock();
+        let _first = locks[0].lock();
+    }
+
+    kprintln!("Consistent lock order passed.");
+
+    // ! This should fail.
+    let (a, b) = &*pair;
+    let _b = b.lock();
+    let _a = a.lock();
+    unreachable!("lockdep should catch the reverse order");
+}
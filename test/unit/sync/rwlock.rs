@@ -0,0 +1,58 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::sync::{Mutex, RwLock};
+use crate::thread::{self, JoinHandle, PRI_DEFAULT};
+
+use super::spawn;
+
+type Order = Arc<Mutex<Vec<&'static str>>>;
+
+/// Spawns a thread that records its `name` in `order` while holding `lock`.
+fn access(
+    name: &'static str,
+    priority: u32,
+    write: bool,
+    lock: &Arc<RwLock<i32>>,
+    order: &Order,
+) -> JoinHandle<()> {
+    let (lock, order) = (lock.clone(), order.clone());
+    spawn(name, priority, move || {
+        if write {
+            let mut guard = lock.write();
+            *guard += 1;
+            order.lock().push(name);
+        } else {
+            let _guard = lock.read();
+            order.lock().push(name);
+        }
+    })
+}
+
+pub fn main() {
+    let lock = Arc::new(RwLock::new(0));
+    let order: Order = Arc::new(Mutex::new(Vec::new()));
+
+    // Readers share the lock.
+    let guard = lock.read();
+    let lock2 = lock.clone();
+    assert_eq!(thread::spawn("reader", move || *lock2.read()).join(), 0);
+
+    // A waiting writer keeps new readers out, and waiting writers get the
+    // lock in the order of priority.
+    let handles = [
+        access("low writer", PRI_DEFAULT + 1, true, &lock, &order),
+        access("reader", PRI_DEFAULT + 3, false, &lock, &order),
+        access("high writer", PRI_DEFAULT + 2, true, &lock, &order),
+    ];
+    assert!(order.lock().is_empty());
+
+    drop(guard);
+    for handle in handles {
+        handle.join();
+    }
+
+    assert_eq!(*order.lock(), ["high writer", "low writer", "reader"]);
+    assert_eq!(*lock.read(), 2);
+    kprintln!("RwLock test done.");
+}
@@ -0,0 +1,50 @@
+use alloc::sync::Arc;
+
+use crate::sbi::timer::timer_ticks;
+use crate::sync::{Condvar, Mutex, Semaphore};
+use crate::thread;
+
+pub fn main() {
+    // Nobody raises it, so the wait ends at the timeout.
+    let sema = Arc::new(Semaphore::new(0));
+    let start = timer_ticks();
+    assert!(sema.down_timeout(5));
+    assert!(timer_ticks() >= start + 5);
+    assert_eq!(sema.value(), 0);
+
+    // Raised before the timeout.
+    let sema2 = sema.clone();
+    let upper = thread::spawn("upper", move || {
+        thread::sleep(2);
+        sema2.up();
+    });
+    assert!(!sema.down_timeout(5));
+    upper.join();
+
+    // The timeout of the last wait, which has not passed yet, must not cut
+    // this one short.
+    let start = timer_ticks();
+    assert!(sema.down_timeout(10));
+    assert!(timer_ticks() >= start + 10);
+
+    let pair: Arc<(Condvar, Mutex<bool>)> = Arc::new((Condvar::new(), Mutex::new(false)));
+    let (cvar, lock) = &*pair;
+    let mut guard = lock.lock();
+    assert!(cvar.wait_timeout(&mut guard, 5));
+    assert!(!*guard);
+
+    let pair2 = pair.clone();
+    let notifier = thread::spawn("notifier", move || {
+        thread::sleep(2);
+        let (cvar, lock) = &*pair2;
+        *lock.lock() = true;
+        cvar.notify_one();
+    });
+    while !*guard {
+        assert!(!cvar.wait_timeout(&mut guard, 100));
+    }
+    drop(guard);
+    notifier.join();
+
+    kprintln!("Timeout test done.");
+}
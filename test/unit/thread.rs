@@ -1,5 +1,7 @@
 pub mod adder;
 pub mod block;
 pub mod bomb;
+pub mod join;
 pub mod spin_interrupt;
 pub mod spin_yield;
+pub mod stack;
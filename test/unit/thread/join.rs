@@ -0,0 +1,26 @@
+use alloc::vec::Vec;
+
+use crate::thread::{self, Status};
+
+const NUM: usize = 8;
+
+pub fn main() {
+    let handles: Vec<_> = (0..NUM)
+        .map(|i| thread::spawn("summer", move || (0..=i * 100).sum::<usize>()))
+        .collect();
+
+    for (i, handle) in handles.into_iter().enumerate() {
+        let thread = handle.thread().clone();
+        let sum = handle.join();
+        assert_eq!(sum, i * 100 * (i * 100 + 1) / 2);
+        assert_eq!(thread.status(), Status::Dying);
+    }
+
+    // Detached threads are reclaimed on their own.
+    drop(thread::spawn("detached", || ()));
+    for _ in 0..NUM {
+        thread::schedule();
+    }
+
+    kprintln!("Join done.");
+}
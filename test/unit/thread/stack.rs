@@ -0,0 +1,29 @@
+use core::hint::black_box;
+
+use crate::mem::PG_SIZE;
+use crate::thread::{Builder, STACK_SIZE};
+
+/// Uses about 100 * 512 bytes of stack, much more than [`STACK_SIZE`].
+const DEPTH: usize = 100;
+
+pub fn main() {
+    let size = PG_SIZE * 16;
+    assert!(DEPTH * 512 > STACK_SIZE && DEPTH * 512 < size);
+
+    let handle = Builder::new(|| deep(DEPTH))
+        .name("deep")
+        .stack_size(size)
+        .spawn();
+    assert_eq!(handle.thread().stack().size(), size);
+    assert_eq!(handle.join(), DEPTH);
+
+    kprintln!("Large stack done.");
+}
+
+fn deep(n: usize) -> usize {
+    let frame = black_box([1u8; 512]);
+    if n == 0 {
+        return 0;
+    }
+    deep(n - 1) + frame[n % 512] as usize
+}
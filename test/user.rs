@@ -22,7 +22,6 @@ const KILLED_USERPROC: [&str; LEN] = [
     "pt-grow-bad",
     "pt-write-code",
 ];
-const KILLED_EXIT: isize = -1;
 const NORMAL_EXIT: isize = 0;
 
 pub fn main(cmd: &str) {
@@ -34,7 +33,7 @@ pub fn main(cmd: &str) {
 
     let r = userproc::wait(userproc::execute(file, argv)).unwrap();
     if KILLED_USERPROC.iter().find(|n| name.eq(**n)).is_some() {
-        assert_eq!(r, KILLED_EXIT);
+        assert_eq!(r, userproc::KILLED_EXIT);
     } else {
         assert_eq!(r, NORMAL_EXIT);
     }
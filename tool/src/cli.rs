@@ -26,6 +26,8 @@ pub enum Commands {
     Test(TestArgs),
     /// Remember specific test cases.
     Book(BookArgs),
+    /// Convert a trace dump into a Chrome trace.
+    Trace(TraceArgs),
 }
 
 /* ---------------------------------- BUILD --------------------------------- */
@@ -81,6 +83,9 @@ pub struct TestArgs {
     /// Verbose mode without testing. Suppress `--gdb` and `--grade`.
     #[arg(short, long)]
     pub verbose: bool,
+    /// Number of harts to run the kernel on, passed to QEMU as `-smp`.
+    #[arg(long)]
+    pub smp: Option<usize>,
 }
 
 /* -------------------------------- BOOKMARK -------------------------------- */
@@ -111,3 +116,17 @@ pub struct BookArgs {
     #[arg(short, long)]
     pub previous_failed: bool,
 }
+
+/* ---------------------------------- TRACE --------------------------------- */
+
+#[derive(Args, Debug)]
+pub struct TraceArgs {
+    /// Output of the kernel built with the `trace` feature, containing a dump.
+    /// The last dump in it is converted.
+    #[arg(short, long)]
+    pub input: String,
+
+    /// The Chrome trace to write, viewable in `chrome://tracing`.
+    #[arg(short, long, default_value = "trace.json")]
+    pub output: String,
+}
@@ -2,6 +2,7 @@ mod book;
 mod build;
 mod cli;
 mod test;
+mod trace;
 
 fn main() -> std::io::Result<()> {
     let cli = cli::Cli::parse();
@@ -9,5 +10,6 @@ fn main() -> std::io::Result<()> {
         cli::Commands::Build(args) => build::main(args),
         cli::Commands::Test(args) => test::main(args),
         cli::Commands::Book(args) => book::main(args),
+        cli::Commands::Trace(args) => trace::main(args),
     }
 }
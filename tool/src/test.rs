@@ -18,6 +18,7 @@ type Runner = fn(&String, Vec<&str>, &mut Record) -> Result<()>;
 static RUNNER: OnceCell<Runner> = OnceCell::new();
 static CTRLC: Lazy<Arc<AtomicBool>> = Lazy::new(|| Arc::new(AtomicBool::new(false)));
 static GDB: OnceCell<bool> = OnceCell::new();
+static SMP: OnceCell<Option<String>> = OnceCell::new();
 
 struct Record(Vec<String>, Vec<String>);
 
@@ -50,6 +51,7 @@ pub fn main(mut args: crate::cli::TestArgs) -> Result<()> {
     } else {
         GDB.get_or_init(|| false);
     }
+    SMP.get_or_init(|| args.smp.map(|n| n.to_string()));
     // Set runner.
     if args.dry {
         RUNNER.get_or_init(|| dry_run);
@@ -83,6 +85,7 @@ fn test_user(cases: Cases, record: &mut Record) -> Result<()> {
             cargo.remove(1);
             cargo.extend(["-s", "-S"].iter());
         }
+        with_smp(&mut cargo);
         let runner = RUNNER.get().unwrap();
         let _ = runner(&k, cargo, record);
         if CTRLC.load(std::sync::atomic::Ordering::SeqCst) {
@@ -102,6 +105,7 @@ fn test_krnl(cases: Cases, record: &mut Record) -> Result<()> {
             cargo.remove(1);
             cargo.extend(["--", "-s", "-S"].iter());
         }
+        with_smp(&mut cargo);
         let runner = RUNNER.get().unwrap();
         let _ = runner(&k, cargo, record);
         if CTRLC.load(std::sync::atomic::Ordering::SeqCst) {
@@ -115,12 +119,18 @@ fn test_krnl(cases: Cases, record: &mut Record) -> Result<()> {
 fn test_schedule(cases: Cases, record: &mut Record) -> Result<()> {
     for (k, v) in cases.0 {
         let args = format!("{}{}{}", &k, if !v.0.is_empty() { " " } else { "" }, &v.0);
+        // Cases of alternative schedulers need their own features.
+        let feature = match k.split('-').next() {
+            Some("mlfqs") => "test-mlfqs",
+            Some("stride") => "test-stride",
+            _ => "test-schedule",
+        };
         let mut cargo = vec![
             "run",
             "-r",
             "-q",
             "-F",
-            "test-schedule",
+            feature,
             "--",
             "-append",
             &args,
@@ -130,6 +140,7 @@ fn test_schedule(cases: Cases, record: &mut Record) -> Result<()> {
             cargo.remove(1);
             cargo.extend(["-s", "-S"].iter());
         }
+        with_smp(&mut cargo);
         let runner = RUNNER.get().unwrap();
         let _ = runner(&k, cargo, record);
         if CTRLC.load(std::sync::atomic::Ordering::SeqCst) {
@@ -140,6 +151,16 @@ fn test_schedule(cases: Cases, record: &mut Record) -> Result<()> {
     Ok(())
 }
 
+/// Append `-smp N` to the QEMU arguments if requested.
+fn with_smp(cargo: &mut Vec<&str>) {
+    if let Some(n) = SMP.get().unwrap() {
+        if !cargo.contains(&"--") {
+            cargo.push("--");
+        }
+        cargo.extend(["-smp", n.as_str()]);
+    }
+}
+
 fn run(case: &String, args: Vec<&str>, record: &mut Record) -> Result<()> {
     use std::io::Write;
     let child = std::process::Command::new("cargo")
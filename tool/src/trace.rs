@@ -0,0 +1,192 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+
+const PREFIX: &str = "[TRACE] ";
+
+/// A record of the kernel's trace buffer, see `src/trace.rs`.
+struct Record {
+    time_us: u64,
+    hart: u64,
+    tid: i64,
+    event: String,
+    arg: Option<String>,
+}
+
+pub fn main(args: crate::cli::TraceArgs) -> Result<()> {
+    let log = fs::read_to_string(&args.input)?;
+    let records = parse(&log)?;
+    let json = to_chrome(&records);
+    fs::write(&args.output, json)?;
+    println!(
+        "Converted {} records into {}, open it in chrome://tracing.",
+        records.len(),
+        args.output
+    );
+    Ok(())
+}
+
+/// Parses the records of the last dump in `log`.
+fn parse(log: &str) -> Result<Vec<Record>> {
+    let lines: Vec<&str> = log
+        .lines()
+        .filter_map(|line| line.find(PREFIX).map(|i| &line[i + PREFIX.len()..]))
+        .collect();
+    let begin = lines
+        .iter()
+        .rposition(|line| line.starts_with("begin"))
+        .ok_or_else(|| invalid("no trace dump found"))?;
+
+    let mut records = Vec::new();
+    for line in &lines[begin + 1..] {
+        if *line == "end" {
+            return Ok(records);
+        }
+        let mut columns = line.split_whitespace();
+        let mut number = || {
+            columns
+                .next()
+                .and_then(|c| c.parse::<i64>().ok())
+                .ok_or_else(|| invalid(&format!("malformed record: {}", line)))
+        };
+        let (time_us, hart, tid) = (number()? as u64, number()? as u64, number()?);
+        let event = columns
+            .next()
+            .ok_or_else(|| invalid(&format!("malformed record: {}", line)))?;
+        records.push(Record {
+            time_us,
+            hart,
+            tid,
+            event: event.to_owned(),
+            arg: columns.next().map(str::to_owned),
+        });
+    }
+    Err(invalid("trace dump is cut off"))
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+/// Builds a Chrome trace with a timeline per thread. Running intervals are
+/// complete events, locks held are async spans, and the others are instants.
+fn to_chrome(records: &[Record]) -> String {
+    let mut events = Vec::new();
+    let mut tids = BTreeSet::new();
+    let start = records.first().map_or(0, |r| r.time_us);
+    let end = records.last().map_or(0, |r| r.time_us);
+    // Running thread of each hart, and since when
+    let mut running: HashMap<u64, (i64, u64)> = HashMap::new();
+
+    let slice = |tid: i64, hart: u64, from: u64, to: u64| {
+        format!(
+            r#"{{"name":"running","ph":"X","pid":0,"tid":{},"ts":{},"dur":{},"args":{{"hart":{}}}}}"#,
+            tid,
+            from,
+            // Harts record on their own, so their records may be out of order.
+            to.saturating_sub(from),
+            hart
+        )
+    };
+    let instant = |r: &Record, tid: i64, name: &str, args: String| {
+        format!(
+            r#"{{"name":"{}","ph":"i","s":"t","pid":0,"tid":{},"ts":{},"args":{{{}}}}}"#,
+            name, tid, r.time_us, args
+        )
+    };
+
+    for r in records {
+        tids.insert(r.tid);
+        let arg = r.arg.as_deref().unwrap_or("");
+        match r.event.as_str() {
+            "switch" => {
+                let since = running.get(&r.hart).map_or(start, |&(_, since)| since);
+                events.push(slice(r.tid, r.hart, since, r.time_us));
+                if let Ok(next) = arg.parse::<i64>() {
+                    tids.insert(next);
+                    running.insert(r.hart, (next, r.time_us));
+                }
+            }
+            "wakeup" => {
+                if let Ok(woken) = arg.parse::<i64>() {
+                    tids.insert(woken);
+                    events.push(instant(r, woken, "wakeup", format!(r#""by":{}"#, r.tid)));
+                }
+            }
+            "block" => events.push(instant(r, r.tid, "block", String::new())),
+            "priority" => events.push(instant(
+                r,
+                r.tid,
+                &format!("priority {}", arg),
+                format!(r#""priority":{}"#, arg),
+            )),
+            "acquire" | "release" => events.push(format!(
+                r#"{{"name":"lock {}","cat":"lock","ph":"{}","id":"{}","pid":0,"tid":{},"ts":{}}}"#,
+                arg,
+                if r.event == "acquire" { "b" } else { "e" },
+                arg,
+                r.tid,
+                r.time_us
+            )),
+            other => events.push(instant(r, r.tid, other, String::new())),
+        }
+    }
+
+    // Threads still running when the buffer was dumped
+    for (hart, (tid, since)) in running {
+        events.push(slice(tid, hart, since, end));
+    }
+    for tid in tids {
+        events.push(format!(
+            r#"{{"name":"thread_name","ph":"M","pid":0,"tid":{},"args":{{"name":"thread {}"}}}}"#,
+            tid, tid
+        ));
+    }
+
+    format!("{{\"traceEvents\":[\n{}\n]}}\n", events.join(",\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\
+[TRACE] begin
+[TRACE] 5 0 1 switch 2
+[TRACE] end
+booting
+[TRACE] begin
+[TRACE] 10 0 1 switch 2
+[TRACE] 12 1 3 wakeup 1
+[TRACE] 11 0 2 block
+[TRACE] end
+";
+
+    #[test]
+    fn parse_last_dump() {
+        let records = parse(LOG).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].time_us, 10);
+        assert_eq!(records[0].event, "switch");
+        assert_eq!(records[0].arg.as_deref(), Some("2"));
+        assert_eq!((records[1].hart, records[1].tid), (1, 3));
+        assert_eq!(records[2].arg, None);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse("no trace here").is_err());
+        assert!(parse("[TRACE] begin\n[TRACE] 1 0 1 block\n").is_err());
+        assert!(parse("[TRACE] begin\n[TRACE] 1 zero 1 block\n[TRACE] end\n").is_err());
+    }
+
+    #[test]
+    fn records_out_of_order() {
+        // Thread 2 was switched to at 12 on hart 0, after the last record at 11.
+        let log = "[TRACE] begin\n[TRACE] 12 0 1 switch 2\n[TRACE] 11 1 3 block\n[TRACE] end\n";
+        let json = to_chrome(&parse(log).unwrap());
+        assert!(json.contains(r#""tid":1,"ts":12,"dur":0"#));
+        assert!(json.contains(r#""tid":2,"ts":12,"dur":0"#));
+        assert!(json.contains(r#""name":"block","ph":"i","s":"t","pid":0,"tid":3,"ts":11"#));
+    }
+}
@@ -0,0 +1,54 @@
+#include "user.h"
+
+static int cas(int* addr, int expected, int desired) {
+    __atomic_compare_exchange_n(addr, &expected, desired, 0, __ATOMIC_ACQUIRE,
+                                __ATOMIC_RELAXED);
+    return expected;
+}
+
+void mutex_init(mutex* m) { m->state = 0; }
+
+void mutex_lock(mutex* m) {
+    int state = cas(&m->state, 0, 1);
+    if (state == 0)
+        return;
+
+    // Mark it contended before blocking, so that the owner wakes us up.
+    if (state != 2)
+        state = __atomic_exchange_n(&m->state, 2, __ATOMIC_ACQUIRE);
+    while (state != 0) {
+        futex_wait(&m->state, 2);
+        state = __atomic_exchange_n(&m->state, 2, __ATOMIC_ACQUIRE);
+    }
+}
+
+/* Returns 1 if the lock was taken. */
+int mutex_trylock(mutex* m) { return cas(&m->state, 0, 1) == 0; }
+
+void mutex_unlock(mutex* m) {
+    if (__atomic_exchange_n(&m->state, 0, __ATOMIC_RELEASE) == 2)
+        futex_wake(&m->state, 1);
+}
+
+void cond_init(condvar* c) { c->seq = 0; }
+
+void cond_wait(condvar* c, mutex* m) {
+    // A signal after the unlock changes seq, so the wait returns at once.
+    int seq = __atomic_load_n(&c->seq, __ATOMIC_RELAXED);
+    mutex_unlock(m);
+    futex_wait(&c->seq, seq);
+
+    // Others may have been woken up as well, so stay contended.
+    while (__atomic_exchange_n(&m->state, 2, __ATOMIC_ACQUIRE) != 0)
+        futex_wait(&m->state, 2);
+}
+
+void cond_signal(condvar* c) {
+    __atomic_add_fetch(&c->seq, 1, __ATOMIC_RELEASE);
+    futex_wake(&c->seq, 1);
+}
+
+void cond_broadcast(condvar* c) {
+    __atomic_add_fetch(&c->seq, 1, __ATOMIC_RELEASE);
+    futex_wake(&c->seq, 0x7fffffff);
+}
@@ -0,0 +1,25 @@
+#ifndef __LIB_LOCK_H
+#define __LIB_LOCK_H
+
+/* Locks on shared memory, which only enter the kernel to block or wake up
+   waiters, through futex_wait and futex_wake. */
+
+typedef struct {
+    int state;  // 0: unlocked, 1: locked, 2: locked with possible waiters
+} mutex;
+
+typedef struct {
+    int seq;  // Bumped by every signal and broadcast
+} condvar;
+
+void mutex_init(mutex* m);
+void mutex_lock(mutex* m);
+int mutex_trylock(mutex* m);
+void mutex_unlock(mutex* m);
+
+void cond_init(condvar* c);
+void cond_wait(condvar* c, mutex* m);
+void cond_signal(condvar* c);
+void cond_broadcast(condvar* c);
+
+#endif
@@ -0,0 +1,94 @@
+#include "user.h"
+
+/* First-fit allocator on the heap grown by sbrk. Free blocks are kept in a
+   circular list sorted by address, so that a freed block merges with its free
+   neighbors. */
+
+typedef union header {
+    struct {
+        union header* next;  // Next free block
+        size_t units;        // Size of the block in headers, this one included
+    } s;
+    char align[16];
+} header;
+
+/* The heap is grown by at least this many units (4 KiB) at a time. */
+#define MIN_UNITS 256
+/* Larger requests would overflow the increment of sbrk. */
+#define MAX_SIZE (1UL << 30)
+
+static header base;  // Empty block, where the free list starts
+static header* freep = NULL;
+
+static header* morecore(size_t units) {
+    if (units < MIN_UNITS)
+        units = MIN_UNITS;
+
+    char* p = sbrk(units * sizeof(header));
+    if (p == (char*)-1)
+        return NULL;
+
+    header* h = (header*)p;
+    h->s.units = units;
+    free(h + 1);
+    return freep;
+}
+
+void* malloc(size_t size) {
+    if (size == 0 || size > MAX_SIZE)
+        return NULL;
+
+    size_t units = (size + sizeof(header) - 1) / sizeof(header) + 1;
+    header* prev = freep;
+    if (prev == NULL) {
+        base.s.next = freep = prev = &base;
+        base.s.units = 0;
+    }
+
+    for (header* p = prev->s.next;; prev = p, p = p->s.next) {
+        if (p->s.units >= units) {
+            if (p->s.units == units) {
+                prev->s.next = p->s.next;
+            } else {
+                // Hand out the tail, so the free list stays as it is.
+                p->s.units -= units;
+                p += p->s.units;
+                p->s.units = units;
+            }
+            freep = prev;
+            return p + 1;
+        }
+        // Wrapped around the free list, nothing fits.
+        if (p == freep && (p = morecore(units)) == NULL)
+            return NULL;
+    }
+}
+
+void free(void* ptr) {
+    if (ptr == NULL)
+        return;
+
+    header* h = (header*)ptr - 1;
+    header* p = freep;
+    // Find the free blocks around it, or the ends of the list.
+    while (!(h > p && h < p->s.next)) {
+        if (p >= p->s.next && (h > p || h < p->s.next))
+            break;
+        p = p->s.next;
+    }
+
+    if (h + h->s.units == p->s.next) {
+        h->s.units += p->s.next->s.units;
+        h->s.next = p->s.next->s.next;
+    } else {
+        h->s.next = p->s.next;
+    }
+
+    if (p + p->s.units == h) {
+        p->s.units += h->s.units;
+        p->s.next = h->s.next;
+    } else {
+        p->s.next = h;
+    }
+    freep = p;
+}
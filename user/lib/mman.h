@@ -0,0 +1,25 @@
+#ifndef __LIB_MMAN_H
+#define __LIB_MMAN_H
+
+/* Memory mappings are made with mmap2 and removed with munmap2. The older
+   mmap and munmap, which map a whole file with the rights it was opened
+   with, are only kept for compatibility.
+
+   Changes to a shared mapping reach the file when a page is evicted or
+   unmapped. Processes mapping the same file don't see each other's changes
+   before that, except for a parent and a child forked after the pages were
+   touched, which share them. */
+
+#define PROT_READ 0x1   // Pages may be read
+#define PROT_WRITE 0x2  // Pages may be written
+#define PROT_EXEC 0x4   // Pages may be executed
+
+#define MAP_SHARED 0x01     // Changes are written back to the file
+#define MAP_PRIVATE 0x02    // Changes are private to the process
+#define MAP_FIXED 0x10      // Place the mapping exactly at the address
+#define MAP_ANONYMOUS 0x20  // Zero-filled memory, without a file
+
+/* Returned by mmap2 on failure. */
+#define MMAP_FAILED ((void*)-1)
+
+#endif
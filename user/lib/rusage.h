@@ -0,0 +1,17 @@
+#ifndef __LIB_RUSAGE_H
+#define __LIB_RUSAGE_H
+
+#include "types.h"
+
+#define RUSAGE_SELF 0       // The calling thread
+#define RUSAGE_CHILDREN -1  // All children waited for, and their own
+
+typedef struct {
+    uint64 utime;   // Timer ticks spent in user mode
+    uint64 stime;   // Timer ticks spent in kernel mode
+    uint64 nvcsw;   // Voluntary context switches
+    uint64 nivcsw;  // Involuntary context switches
+    uint64 nfault;  // Page faults
+} rusage;
+
+#endif
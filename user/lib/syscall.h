@@ -18,3 +18,31 @@
 /* Project 4 only. */
 #define SYS_CHDIR 15 /**< Change the current directory. */
 #define SYS_MKDIR 16 /**< Create a directory. */
+
+/* Scheduling. */
+#define SYS_NICE 17    /**< Set the nice value of this thread. */
+#define SYS_GETNICE 18 /**< Get the nice value of this thread. */
+#define SYS_SLEEP 19   /**< Sleep for some milliseconds. */
+
+/* Accounting. */
+#define SYS_GETRUSAGE 20 /**< Get resource usage of this thread or its children. */
+
+/* Processes. */
+#define SYS_KILL 21    /**< Terminate another process. */
+#define SYS_WAITPID 22 /**< Wait for a child process, with a timeout. */
+
+/* User-space synchronization. */
+#define SYS_FUTEX_WAIT 23 /**< Block while a word holds a value. */
+#define SYS_FUTEX_WAKE 24 /**< Wake up threads blocked on a word. */
+
+/* Process creation. */
+#define SYS_FORK 25   /**< Clone the calling process, copy-on-write. */
+#define SYS_EXECVE 26 /**< Replace the image of the calling process. */
+
+/* Heap. */
+#define SYS_BRK 27  /**< Set the program break. */
+#define SYS_SBRK 28 /**< Move the program break, returning the old one. */
+
+/* Memory mappings. */
+#define SYS_MMAP2 29   /**< Map a file or anonymous memory, with flags. */
+#define SYS_MUNMAP2 30 /**< Remove the mappings in an address range. */
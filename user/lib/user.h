@@ -5,13 +5,18 @@
 
 #include "fcntl.h"
 #include "fstat.h"
+#include "lock.h"
+#include "mman.h"
+#include "rusage.h"
 #include "types.h"
+#include "wait.h"
 
 #define NULL ((void*)0)
 #define ROUND_UP(p, align) (((uint64)p + (align)-1) / (align) * (align))
 #define ROUND_DOWN(p, align) ((uint64)p / (align) * (align))
 #define PANIC_EXIT 12345
 #define NORMAL_EXIT 0
+#define KILLED_EXIT (-1)
 
 #define panic(fmt, args...)                                                       \
     do {                                                                          \
@@ -42,6 +47,20 @@ int mmap(int fd, void* addr);
 void munmap(int mapid);
 int chdir(const char* dir);
 int mkdir(const char* dir);
+int nice(int nice);
+int getnice(void);
+void sleep(int ms);
+int getrusage(int who, rusage* usage);
+int kill(int pid);
+int waitpid(int pid, exit_status* status, int timeout_ms);
+int futex_wait(int* addr, int expected);
+int futex_wake(int* addr, int n);
+int fork(void);
+int execve(const char* pathname, const char* argv[]);
+int brk(void* addr);
+void* sbrk(long increment);
+void* mmap2(void* addr, size_t length, int prot, int flags, int fd, size_t offset);
+int munmap2(void* addr, size_t length);
 
 // ulib.c
 void fprintf(int fd, const char* fmt, ...);
@@ -58,4 +77,8 @@ void check_file(const char*, const void* buf, size_t);
 void check_file_handle(int fd, const char* file_name, const void* buf_, size_t size);
 uint64 r_sp();
 
+// malloc.c
+void* malloc(size_t size);
+void free(void* ptr);
+
 #endif
@@ -30,3 +30,17 @@ entry("mmap");
 entry("munmap");
 entry("chdir");
 entry("mkdir");
+entry("nice");
+entry("getnice");
+entry("sleep");
+entry("getrusage");
+entry("kill");
+entry("waitpid");
+entry("futex_wait");
+entry("futex_wake");
+entry("fork");
+entry("execve");
+entry("brk");
+entry("sbrk");
+entry("mmap2");
+entry("munmap2");
@@ -0,0 +1,9 @@
+#ifndef __LIB_WAIT_H
+#define __LIB_WAIT_H
+
+typedef struct {
+    int value;   // Exit value, KILLED_EXIT if killed
+    int killed;  // Whether it was killed by the kernel or by kill()
+} exit_status;
+
+#endif
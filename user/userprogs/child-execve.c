@@ -0,0 +1,15 @@
+/** Image loaded by execve-simple. The file descriptors in argv[1] and
+   argv[2] were opened before execve, the latter with O_CLOEXEC. */
+
+#include "user.h"
+
+void main(int argc, char* argv[]) {
+    char buf[8];
+
+    assert(argc == 3);
+    assert(strcmp("child-execve", argv[0]) == 0);
+    assert(read(atoi(argv[1]), buf, sizeof buf) == sizeof buf);
+    assert(read(atoi(argv[2]), buf, sizeof buf) == -1);
+
+    exit(81);
+}
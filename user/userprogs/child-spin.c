@@ -0,0 +1,9 @@
+/** Child process run by the kill tests.
+   Spins forever, so it only ends by being killed. */
+
+#include "user.h"
+
+void main() {
+    for (;;)
+        ;
+}
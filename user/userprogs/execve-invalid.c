@@ -0,0 +1,14 @@
+/** A failed execve returns -1, leaving the caller running as before. */
+
+#include "user.h"
+
+void main() {
+    int local = 7;
+    const char* args[] = {"sample.txt", NULL};
+
+    assert(execve("non-exist", args) == -1);
+    assert(execve(args[0], args) == -1, "\"sample.txt\" is not executable");
+    assert(execve("child-simple", NULL) == -1);
+    assert(execve((const char*)0xffffffc030000000, args) == -1);
+    assert(local == 7);
+}
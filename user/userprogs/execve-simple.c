@@ -0,0 +1,23 @@
+/** A forked child replaces its image with child-execve, keeping its pid
+   and the file descriptors not opened with O_CLOEXEC. */
+
+#include "user.h"
+
+void main() {
+    int pid, kept, closed;
+    char kept_str[12], closed_str[12];
+
+    if ((pid = fork()) == 0) {
+        assert((kept = open("sample.txt", O_RDONLY)) > 2);
+        assert((closed = open("sample.txt", O_RDONLY | O_CLOEXEC)) > 2);
+        itoa(kept_str, kept);
+        itoa(closed_str, closed);
+
+        const char* args[] = {"child-execve", kept_str, closed_str, 0};
+        execve(args[0], args);
+        panic("execve returned");
+    }
+
+    assert(pid > 0);
+    assert(wait(pid) == 81);
+}
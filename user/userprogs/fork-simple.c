@@ -0,0 +1,31 @@
+/** Forks a child, which sees a copy of the parent's memory and open files.
+   Writes on either side must not be seen by the other. */
+
+#include "user.h"
+
+int global = 42;
+
+void main() {
+    int pid, local = 1, fd;
+    char buf[8];
+
+    // Make sure the data page is loaded, and shared by the fork.
+    global++;
+    assert((fd = open("sample.txt", O_RDONLY)) > 2);
+
+    if ((pid = fork()) == 0) {
+        assert(global == 43 && local == 1);
+        global = 100;
+        local = 2;
+        assert(global == 100 && local == 2);
+        assert(read(fd, buf, sizeof buf) == sizeof buf);
+        exit(global + local);
+    }
+
+    assert(pid > 0);
+    local = 3;
+    assert(wait(pid) == 102);
+    assert(global == 43 && local == 3);
+    assert(read(fd, buf, sizeof buf) == sizeof buf);
+    close(fd);
+}
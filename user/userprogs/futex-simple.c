@@ -0,0 +1,28 @@
+/** Uses the futex calls and the locks built on them without contention. A
+   wait on a changed value returns at once, and bad addresses are refused. */
+
+#include "user.h"
+
+void main() {
+    int word = 1;
+    mutex m;
+    condvar c;
+
+    assert(futex_wait(&word, 0) == -1);
+    assert(futex_wake(&word, 1) == 0);
+    assert(futex_wait((int*)((char*)&word + 1), 1) == -1);
+    assert(futex_wake((int*)0xffffffc080200000, 1) == -1);
+    assert(futex_wait(NULL, 0) == -1);
+
+    mutex_init(&m);
+    mutex_lock(&m);
+    assert(!mutex_trylock(&m));
+    mutex_unlock(&m);
+    assert(mutex_trylock(&m));
+    mutex_unlock(&m);
+
+    cond_init(&c);
+    cond_signal(&c);
+    cond_broadcast(&c);
+    assert(c.seq == 2);
+}
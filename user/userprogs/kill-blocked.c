@@ -0,0 +1,22 @@
+/** Kills a child while it's blocked waiting for its own child, which sleeps
+   for a while before exiting normally. The child should die right away,
+   instead of returning from wait first. */
+
+#include "user.h"
+
+void main(int argc, char* argv[]) {
+    if (argc == 2) {
+        const char* args[] = {"sleep-simple", 0};
+        wait(exec(args[0], args));
+        exit(NORMAL_EXIT);
+    }
+
+    int pid;
+    const char* args[] = {"kill-blocked", "child", 0};
+
+    assert((pid = exec(args[0], args)) >= 0);
+    sleep(100);
+
+    assert(kill(pid) == 0);
+    assert(wait(pid) == KILLED_EXIT);
+}
@@ -0,0 +1,17 @@
+/** Kills a child that never exits by itself. The kill should be reported
+   by wait, and the pid should be gone afterwards. */
+
+#include "user.h"
+
+void main() {
+    int pid;
+    const char* args[] = {"child-spin", 0};
+
+    assert((pid = exec(args[0], args)) >= 0);
+    sleep(100);
+
+    assert(kill(pid) == 0);
+    assert(wait(pid) == KILLED_EXIT);
+    assert(kill(pid) == -1);
+    assert(kill(-1) == -1);
+}
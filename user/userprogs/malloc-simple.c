@@ -0,0 +1,44 @@
+/** Allocates blocks of various sizes on the heap, checks that they don't
+   overlap, then frees them and makes sure the memory is reused. */
+
+#include "user.h"
+
+#define N 64
+
+void main() {
+    char* blocks[N];
+
+    for (int i = 0; i < N; i++) {
+        int size = 1 + i * 97;
+        blocks[i] = malloc(size);
+        assert(blocks[i] != NULL);
+        assert((uint64)blocks[i] % 16 == 0);
+        memset(blocks[i], i, size);
+    }
+    for (int i = 0; i < N; i++)
+        for (int j = 0; j < 1 + i * 97; j++)
+            assert(blocks[i][j] == (char)i);
+
+    // Freed memory is merged and reused, instead of growing the heap.
+    char* top = sbrk(0);
+    for (int i = 0; i < N; i += 2)
+        free(blocks[i]);
+    for (int i = 1; i < N; i += 2)
+        free(blocks[i]);
+    char* big = malloc(N * 97);
+    assert(big != NULL);
+    memset(big, 0xff, N * 97);
+    assert(sbrk(0) == top);
+    free(big);
+
+    // Large blocks span many pages.
+    int* array = malloc(8 * 4096 * sizeof(int));
+    assert(array != NULL);
+    for (int i = 0; i < 8 * 4096; i++)
+        array[i] = i;
+    for (int i = 0; i < 8 * 4096; i++)
+        assert(array[i] == i);
+    free(array);
+
+    assert(malloc(0) == NULL);
+}
@@ -0,0 +1,28 @@
+/** Tests the getrusage system call on this process and on its children. */
+
+#include "user.h"
+
+void main() {
+    rusage self, children;
+
+    assert(getrusage(RUSAGE_CHILDREN, &children) == 0);
+    assert(children.utime == 0 && children.nfault == 0);
+
+    /* Our pages are loaded lazily, so we must have faulted already. */
+    assert(getrusage(RUSAGE_SELF, &self) == 0);
+    assert(self.nfault > 0);
+
+    /* Spin until a timer tick lands in user mode. */
+    while (self.utime == 0) {
+        for (volatile int i = 0; i < 100000; i++)
+            ;
+        getrusage(RUSAGE_SELF, &self);
+    }
+
+    const char* argv[] = {"child-simple", 0};
+    assert(wait(exec(argv[0], argv)) == 81);
+    assert(getrusage(RUSAGE_CHILDREN, &children) == 0);
+    assert(children.nfault > 0);
+
+    assert(getrusage(1, &self) == -1);
+}
@@ -0,0 +1,37 @@
+/** Grows and shrinks the heap. New heap pages read as zeros, pages released
+   by shrinking come back zeroed, and the break can't go below its start. */
+
+#include "user.h"
+
+#define PGSIZE 4096
+
+void main() {
+    char* start = sbrk(0);
+    assert(start != (char*)-1);
+    assert((uint64)start % PGSIZE == 0);
+
+    // Grow by three pages and a bit, and touch all of them.
+    assert(sbrk(3 * PGSIZE + 100) == start);
+    assert(sbrk(0) == start + 3 * PGSIZE + 100);
+    for (int i = 0; i < 3 * PGSIZE + 100; i++) {
+        assert(start[i] == 0);
+        start[i] = (char)i;
+    }
+    for (int i = 0; i < 3 * PGSIZE + 100; i++)
+        assert(start[i] == (char)i);
+
+    // Give back the last two pages, then take them again.
+    assert(brk(start + PGSIZE) == 0);
+    assert(sbrk(0) == start + PGSIZE);
+    assert(sbrk(2 * PGSIZE) == start + PGSIZE);
+    assert(start[PGSIZE - 1] == (char)(PGSIZE - 1));
+    for (int i = PGSIZE; i < 3 * PGSIZE; i++)
+        assert(start[i] == 0);
+
+    // The heap can't shrink below its start.
+    assert(brk(start - PGSIZE) == -1);
+    assert(sbrk(-4 * PGSIZE) == (char*)-1);
+    assert(sbrk(0) == start + 3 * PGSIZE);
+
+    assert(brk(start) == 0);
+}
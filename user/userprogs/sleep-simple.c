@@ -0,0 +1,23 @@
+/** Tests the sleep system call, including zero and negative durations.
+   A child spins while we sleep, and the time it got shows that we slept. */
+
+#include "user.h"
+
+void main() {
+    int pid;
+    const char* args[] = {"child-spin", 0};
+    rusage children;
+
+    sleep(0);
+    sleep(-100);
+
+    assert((pid = exec(args[0], args)) >= 0);
+    sleep(300);
+    printf("Woke up\n");
+
+    /* 300 ms are 3 ticks, allow one for rounding at either end. */
+    assert(kill(pid) == 0);
+    wait(pid);
+    assert(getrusage(RUSAGE_CHILDREN, &children) == 0);
+    assert(children.utime + children.stime >= 2, "slept for %d ticks", children.utime + children.stime);
+}
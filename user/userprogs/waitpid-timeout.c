@@ -0,0 +1,27 @@
+/** Waits for a child that never exits by itself with a timeout, which should
+   expire, then kills it and waits again for its exit value. The kill should
+   be told apart from a child that exits by itself. */
+
+#include "user.h"
+
+void main() {
+    int pid;
+    exit_status status = {0, 0};
+    const char* args[] = {"child-spin", 0};
+    const char* simple[] = {"child-simple", 0};
+
+    assert((pid = exec(args[0], args)) >= 0);
+
+    assert(waitpid(pid, &status, 0) == 0);
+    assert(waitpid(pid, &status, 100) == 0);
+    assert(status.value == 0 && !status.killed);
+
+    assert(kill(pid) == 0);
+    assert(waitpid(pid, &status, -1) == pid);
+    assert(status.value == KILLED_EXIT && status.killed);
+    assert(waitpid(-1, &status, 100) == -1);
+
+    assert((pid = exec(simple[0], simple)) >= 0);
+    assert(waitpid(pid, &status, -1) == pid);
+    assert(status.value == 81 && !status.killed);
+}
@@ -0,0 +1,66 @@
+/* Forks a child that shares a page of a shared mapping with its parent. The
+   child blocks on a futex in it until the parent wakes it up, then both take
+   turns through a mutex and a condition variable in the same page. */
+
+#include "user.h"
+
+#define PGSIZE 4096
+#define ROUNDS 10
+
+struct shared {
+    int word;
+    mutex m;
+    condvar c;
+    int turn;
+    int count;
+};
+
+void main() {
+    int fd, pid, i;
+    struct shared zero = {0};
+    struct shared* s;
+
+    assert((fd = open("futex.tmp", O_CREATE | O_RDWR)) > 2);
+    assert(write(fd, &zero, sizeof zero) == sizeof zero);
+    s = mmap2(NULL, PGSIZE, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0);
+    assert(s != MMAP_FAILED);
+
+    /* Touch the page, so that the child shares it. */
+    mutex_init(&s->m);
+    cond_init(&s->c);
+
+    if ((pid = fork()) == 0) {
+        assert(futex_wait(&s->word, 0) == 0, "woken up by the parent");
+        for (i = 0; i < ROUNDS; i++) {
+            mutex_lock(&s->m);
+            while (s->turn != 1)
+                cond_wait(&s->c, &s->m);
+            s->count++;
+            s->turn = 0;
+            cond_signal(&s->c);
+            mutex_unlock(&s->m);
+        }
+        exit(81);
+    }
+    assert(pid > 0);
+
+    /* Only a child blocked on the word can be woken up. */
+    while (futex_wake(&s->word, 1) == 0)
+        sleep(10);
+
+    for (i = 0; i < ROUNDS; i++) {
+        mutex_lock(&s->m);
+        while (s->turn != 0)
+            cond_wait(&s->c, &s->m);
+        s->count++;
+        s->turn = 1;
+        cond_signal(&s->c);
+        mutex_unlock(&s->m);
+    }
+
+    assert(wait(pid) == 81);
+    assert(s->count == 2 * ROUNDS, "both processes see the same page");
+    assert(munmap2(s, PGSIZE) == 0);
+    close(fd);
+    assert(remove("futex.tmp") == 0);
+}
@@ -0,0 +1,54 @@
+/* Maps anonymous memory where the kernel chooses, and at a fixed address.
+   Checks that its pages start out as zeros, that mappings don't overlap, and
+   that an unmapped range can be mapped again. */
+
+#include "user.h"
+
+#define PGSIZE 4096
+#define FIXED ((char*)0x20000000)
+
+void main() {
+    int prot = PROT_READ | PROT_WRITE;
+    int flags = MAP_PRIVATE | MAP_ANONYMOUS;
+    char *p, *q;
+    int i;
+
+    /* Let the kernel place it. */
+    assert((p = mmap2(NULL, 3 * PGSIZE, prot, flags, -1, 0)) != MMAP_FAILED);
+    assert((uint64)p % PGSIZE == 0);
+    for (i = 0; i < 3 * PGSIZE; i++) {
+        assert(p[i] == 0, "anonymous pages start out as zeros");
+        p[i] = (char)i;
+    }
+    for (i = 0; i < 3 * PGSIZE; i++)
+        assert(p[i] == (char)i);
+
+    /* A second mapping goes somewhere else. */
+    assert((q = mmap2(NULL, PGSIZE, prot, flags, -1, 0)) != MMAP_FAILED);
+    assert(q + PGSIZE <= p || q >= p + 3 * PGSIZE);
+    memset(q, 0xff, PGSIZE);
+    assert(p[0] == 0 && p[3 * PGSIZE - 1] == (char)(3 * PGSIZE - 1));
+
+    /* Fixed addresses are taken as they are, and must be free. */
+    assert(mmap2(FIXED, PGSIZE, prot, flags | MAP_FIXED, -1, 0) == FIXED);
+    assert(mmap2(FIXED, PGSIZE, prot, flags | MAP_FIXED, -1, 0) == MMAP_FAILED);
+    assert(mmap2(p + PGSIZE, PGSIZE, prot, flags | MAP_FIXED, -1, 0) == MMAP_FAILED);
+    FIXED[PGSIZE - 1] = 1;
+
+    /* Bad arguments. */
+    assert(mmap2(NULL, 0, prot, flags, -1, 0) == MMAP_FAILED);
+    assert(mmap2(NULL, PGSIZE, 0, flags, -1, 0) == MMAP_FAILED);
+    assert(mmap2(NULL, PGSIZE, prot, MAP_SHARED | MAP_ANONYMOUS, -1, 0) == MMAP_FAILED);
+    assert(mmap2(NULL, PGSIZE, prot, MAP_ANONYMOUS, -1, 0) == MMAP_FAILED);
+
+    /* Mappings are unmapped whole, then their range is free again. */
+    assert(munmap2(p + PGSIZE, PGSIZE) == -1);
+    assert(munmap2(p, 3 * PGSIZE) == 0);
+    assert(mmap2(p, 3 * PGSIZE, prot, flags | MAP_FIXED, -1, 0) == p);
+    for (i = 0; i < 3 * PGSIZE; i++)
+        assert(p[i] == 0, "pages are zeros again after being unmapped");
+
+    assert(munmap2(FIXED, PGSIZE) == 0);
+    assert(munmap2(q, PGSIZE) == 0);
+    assert(munmap2(p, 3 * PGSIZE) == 0);
+}
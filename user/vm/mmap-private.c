@@ -0,0 +1,50 @@
+/* Maps "sample.txt" privately and shared. Writes to a private mapping stay
+   out of the file, while those to a shared one end up in it. */
+
+#include "sample.inc"
+#include "user.h"
+
+#define PGSIZE 4096
+
+void main() {
+    int fd, len = strlen(sample);
+    char *private, *shared;
+    char buf[1024];
+    int i;
+
+    assert((fd = open("sample.txt", O_RDONLY)) > 2);
+
+    /* A read-only file can't be written back to, but can be copied. */
+    assert(mmap2(NULL, len, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0) == MMAP_FAILED);
+    assert((private = mmap2(NULL, len, PROT_READ | PROT_WRITE, MAP_PRIVATE, fd, 0)) != MMAP_FAILED);
+    assert(memcmp(private, sample, len) == 0, "private mapping reads the file");
+    memset(private, 'x', len);
+
+    assert((shared = mmap2(NULL, len, PROT_READ, MAP_SHARED, fd, 0)) != MMAP_FAILED);
+    assert(memcmp(shared, sample, len) == 0, "private writes stay out of the file");
+    assert(munmap2(private, len) == 0);
+    assert(munmap2(shared, len) == 0);
+    check_file_handle(fd, "sample.txt", sample, len);
+
+    /* Offsets must be aligned, and pages past the end of the file are zeros. */
+    assert(mmap2(NULL, PGSIZE, PROT_READ, MAP_PRIVATE, fd, 1) == MMAP_FAILED);
+    assert((private = mmap2(NULL, PGSIZE, PROT_READ, MAP_PRIVATE, fd, PGSIZE)) != MMAP_FAILED);
+    for (i = 0; i < PGSIZE; i++)
+        assert(private[i] == 0);
+    assert(munmap2(private, PGSIZE) == 0);
+    close(fd);
+
+    /* Shared writes go to the file when unmapped. */
+    assert((fd = open("sample.txt", O_RDWR)) > 2);
+    assert((shared = mmap2(NULL, len, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0)) != MMAP_FAILED);
+    memset(shared, 42, len);
+    assert(munmap2(shared, len) == 0);
+    read(fd, buf, len);
+    for (i = 0; i < len; i++)
+        assert(buf[i] == 42, "check that shared write was successful");
+
+    /* Write origin content back */
+    seek(fd, 0);
+    write(fd, sample, len);
+    close(fd);
+}